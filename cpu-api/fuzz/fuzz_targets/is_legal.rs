@@ -0,0 +1,19 @@
+#![no_main]
+
+use cpu_api::fork::ACTION_MARKS;
+use cpu_api::Forker;
+use libfuzzer_sys::fuzz_target;
+
+// one `-A` action such as `a+b`, `b-` or `b=ls`: every process name it
+// gives back is non-empty and free of the action marks
+fuzz_target!(|action: &str| {
+    let forker = Forker::builder().build();
+    if let Ok(names) = forker.is_legal(action) {
+        // an exec gives back its process and then its program
+        let processes = match action.contains('=') {
+            true => &names[..1],
+            false => &names[..],
+        };
+        assert!(processes.iter().all(|name| !name.is_empty() && !name.contains(ACTION_MARKS)));
+    }
+});
@@ -0,0 +1,209 @@
+//! The command lines of `fork.py` and `generator.py`, shared by the
+//! `cpu-api` binary and `ostep fork` / `ostep generator`.
+
+use std::fs::File;
+use std::io::{self, Write};
+
+use clap::Args;
+use ostep_rng::Random;
+
+use crate::fork::Forker;
+use crate::generator::{self, Action, CodeGeneratorReadable, CodeGeneratorRunnable};
+use crate::procfs;
+
+/// The smallest `-f`; with rarer forks, random actions keep drawing exits of
+/// the root, which cannot exit, and take effectively forever.
+pub const MIN_FORK_PERCENTAGE: f64 = 0.01;
+
+/// Options of `fork.py` but the seed and `-c`, which the binaries share
+/// with the other tools.
+#[derive(Args, Debug)]
+pub struct ForkOptions {
+    /// percent of actions that are forks (not exits), from 0.01 to 1
+    #[arg(short = 'f', long = "forks", default_value_t = 0.7)]
+    pub fork_percentage: f64,
+
+    /// number of random actions to generate
+    #[arg(short = 'a', long = "actions", default_value_t = 5)]
+    pub max_actions: usize,
+
+    /// actions to run instead of random ones, e.g. `a+b,b=ls,b-`: `X+Y`
+    /// means X forks Y, `X-` means X exits, `X*` means X waits for a child
+    /// (with -Z), `X^` means X becomes a subreaper (heeded with -S), `X=P`
+    /// means X execs program P, `X#` means X starts its own process group,
+    /// `X!` means X is killed and `X%` means group X is killed
+    #[arg(short = 'A', long = "action_list", default_value = "")]
+    pub action_list: String,
+
+    /// show the trees and ask for the actions, instead of the other way round
+    #[arg(short = 't', long = "show_tree")]
+    pub show_tree: bool,
+
+    /// only show the final tree, not the ones in between
+    #[arg(short = 'F', long = "final_only")]
+    pub just_final: bool,
+
+    /// only let processes without children exit
+    #[arg(short = 'L', long = "leaf_only")]
+    pub leaf_only: bool,
+
+    /// give the orphans of an exiting process to its parent, not the root
+    #[arg(short = 'R', long = "local_reparent")]
+    pub local_reparent: bool,
+
+    /// give the children of an exiting process to its nearest ancestor which
+    /// became a subreaper, else the root, as Linux does
+    #[arg(short = 'S', long, conflicts_with = "local_reparent")]
+    pub subreaper: bool,
+
+    /// kill the descendants of killed processes too, instead of orphaning
+    /// them
+    #[arg(short = 'K', long = "kill_descendants")]
+    pub kill_descendants: bool,
+
+    /// keep exited processes as zombies until their parent waits for them
+    /// (a wait blocks until a child exits); the root reaps the orphans it
+    /// adopts
+    #[arg(short = 'Z', long)]
+    pub zombies: bool,
+
+    /// print the live tree of processes under PID, read from /proc, instead
+    /// of running actions
+    #[arg(short = 'p', long)]
+    pub pid: Option<u32>,
+
+    /// run COMMAND under ptrace and replay its forks, execs and exits
+    /// instead of random actions; it must come last, and what COMMAND
+    /// prints goes to stderr
+    #[arg(
+        short = 'T',
+        long,
+        value_name = "COMMAND",
+        num_args = 1..,
+        allow_hyphen_values = true,
+        conflicts_with = "action_list"
+    )]
+    pub trace: Vec<String>,
+
+    /// how to draw trees; `dot` (Graphviz) and `mermaid` draw the whole run
+    /// as one diagram, answers included
+    #[arg(
+        short = 'P',
+        long = "print_style",
+        default_value = "fancy",
+        value_parser = ["basic", "line1", "line2", "fancy", "dot", "mermaid"]
+    )]
+    pub print_style: String,
+}
+
+/// Do what `fork.py` does with these options, writing to `out`.
+///
+/// # Errors
+///
+/// Fails on options which make no sense, on illegal actions and when the
+/// output cannot be written.
+pub fn fork<W: Write>(options: &ForkOptions, seed: u64, solve: bool, out: &mut W) -> Result<(), String> {
+    // -f only matters to random actions
+    let random = options.trace.is_empty() && options.action_list.is_empty() && options.pid.is_none();
+    if random && !(MIN_FORK_PERCENTAGE..=1.0).contains(&options.fork_percentage) {
+        return Err(format!("fork percentage must be between {} and 1", MIN_FORK_PERCENTAGE));
+    }
+
+    let action_list = match (options.trace.is_empty(), options.action_list.is_empty()) {
+        (false, _) => trace(&options.trace)?,
+        (true, true) => Vec::new(),
+        (true, false) => options.action_list.split(',').map(str::to_string).collect(),
+    };
+
+    let builder = Forker::builder()
+        .fork_percentage(options.fork_percentage)
+        .max_actions(options.max_actions)
+        .action_list(action_list)
+        .show_tree(options.show_tree)
+        .just_final(options.just_final)
+        .leaf_only(options.leaf_only)
+        .local_reparent(options.local_reparent)
+        .zombies(options.zombies)
+        .subreaper(options.subreaper)
+        .kill_descendants(options.kill_descendants)
+        .print_style(&options.print_style)
+        .solve(solve)
+        .rng(Random::python(seed));
+
+    match options.pid {
+        Some(pid) => procfs::read_tree(&builder, pid)?
+            .write_tree(out)
+            .map_err(|e| format!("Failed to write trees: {}", e)),
+        None => builder.build().run(out),
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn trace(command: &[String]) -> Result<Vec<String>, String> {
+    crate::trace::trace_command(command)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn trace(_command: &[String]) -> Result<Vec<String>, String> {
+    Err("tracing needs Linux".to_string())
+}
+
+/// Options of `generator.py` but the seed and `-c`.
+#[derive(Args, Debug)]
+pub struct GeneratorOptions {
+    /// what the root process `a` does, e.g. `fork b(sleep 1,fork c),wait`:
+    /// `fork X(...)` forks X which does what is in brackets and exits,
+    /// `wait` waits for a child and `sleep N` sleeps N seconds; random
+    /// actions are drawn when empty
+    #[arg(short = 'A', long = "actions", default_value = "")]
+    pub actions: String,
+
+    /// number of forks of the random actions (at most 25)
+    #[arg(short = 'n', long = "numforks", default_value_t = 3)]
+    pub num_forks: usize,
+
+    /// write the runnable version, which prints a timeline of its processes
+    #[arg(short = 'R', long)]
+    pub runnable: bool,
+
+    /// write the program to `<OUT>.c` instead of printing it
+    #[arg(short = 'o', long)]
+    pub out: Option<String>,
+}
+
+/// Write the C program for the given or random actions; with `solve` the
+/// program starts with a comment listing the actions.
+///
+/// # Errors
+///
+/// Fails on actions which do not parse and when the program cannot be
+/// written.
+pub fn generator<W: Write>(options: &GeneratorOptions, seed: u64, solve: bool, out: &mut W) -> Result<(), String> {
+    let actions = match options.actions.is_empty() {
+        true => generator::random_actions(&mut Random::python(seed), options.num_forks),
+        false => generator::parse_actions(&options.actions)?,
+    };
+
+    let name = options.out.clone().unwrap_or_else(|| "generated".to_string());
+    let write_failed = |e: io::Error| format!("Failed to write {}.c: {}", name, e);
+
+    match &options.out {
+        Some(_) => {
+            let fd = File::create(format!("{}.c", name)).map_err(write_failed)?;
+            write_program(fd, &name, actions, options.runnable, solve).map_err(write_failed)
+        },
+        None => write_program(out, &name, actions, options.runnable, solve).map_err(write_failed),
+    }
+}
+
+fn write_program<W: Write>(mut fd: W, name: &str, actions: Vec<Action>, runnable: bool, solve: bool) -> io::Result<()> {
+    if solve {
+        let actions = actions.iter().map(|a| a.to_string()).collect::<Vec<String>>();
+        writeln!(fd, "// actions: {}", actions.join(","))?;
+    }
+
+    match runnable {
+        true => CodeGeneratorRunnable::new(fd, name.to_string(), actions).generate(),
+        false => CodeGeneratorReadable::new(fd, name.to_string(), actions).generate(),
+    }
+}
@@ -0,0 +1,83 @@
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use crate::cli::{self, ForkOptions, GeneratorOptions};
+
+    #[derive(Parser)]
+    struct ForkArgs {
+        #[command(flatten)]
+        options: ForkOptions,
+    }
+
+    #[derive(Parser)]
+    struct GeneratorArgs {
+        #[command(flatten)]
+        options: GeneratorOptions,
+    }
+
+    fn fork(args: &[&str]) -> Result<String, String> {
+        let args = ForkArgs::try_parse_from([&["fork"], args].concat()).map_err(|e| e.to_string())?;
+        let mut out = Vec::new();
+        cli::fork(&args.options, 0, true, &mut out)?;
+        Ok(String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn test_fork_options() {
+        let args = ForkArgs::try_parse_from(["fork"]).unwrap().options;
+        assert_eq!(args.fork_percentage, 0.7);
+        assert_eq!(args.max_actions, 5);
+        assert_eq!(args.print_style, "fancy");
+
+        let args = ForkArgs::try_parse_from(["fork", "-f", "0.3", "-a", "9", "-A", "a+b", "-t", "-F", "-L", "-R", "-P", "line2"])
+            .unwrap()
+            .options;
+        assert_eq!(args.fork_percentage, 0.3);
+        assert_eq!(args.max_actions, 9);
+        assert_eq!(args.action_list, "a+b");
+        assert!(args.show_tree && args.just_final && args.leaf_only && args.local_reparent);
+        assert_eq!(args.print_style, "line2");
+    }
+
+    #[test]
+    fn test_fork_runs() {
+        assert!(fork(&[]).is_ok());
+        assert!(fork(&["-f", "0.01", "-a", "20"]).is_ok());
+        assert!(fork(&["-f", "0", "-A", "a+b"]).is_ok());
+        assert!(fork(&["-a", "200", "-f", "0.5", "-P", "basic"]).is_ok());
+        assert!(fork(&["-A", "a+b,b+c,c+d,b-", "-R"]).is_ok());
+        assert!(fork(&["-A", "a+b,b+c,b-", "-L", "-F", "-t"]).is_ok());
+        assert!(fork(&["-A", "a+b,b^,b+c,c+d,c-", "-S"]).unwrap().contains("Action: b becomes a subreaper\n"));
+        assert!(fork(&["-A", "a+b,b+c,b#,b!", "-K"]).unwrap().contains("Action: b is killed, with c\n"));
+
+        let final_tree = fork(&["-A", "a+b", "-F", "-P", "line1"]).unwrap();
+        assert!(final_tree.ends_with("Final Process Tree:\na\n|-- b\n\n"), "{}", final_tree);
+
+        let trace = fork(&["-A", "a+b,b+c,b-", "-L", "-P", "line1"]).unwrap();
+        assert!(trace.contains("Action: b EXITS (failed: has children)\na\n|-- b\n    |-- c\n"), "{}", trace);
+    }
+
+    #[test]
+    fn test_fork_bad_options_are_errors() {
+        assert!(fork(&["-f", "0"]).is_err());
+        assert!(fork(&["-f", "1e-300"]).is_err());
+        assert!(fork(&["-f", "1.5"]).is_err());
+        assert!(fork(&["-f", "NaN"]).is_err());
+        assert!(fork(&["-P", "boxes"]).is_err());
+        assert!(fork(&["-a", "-1"]).is_err());
+        assert_eq!(fork(&["-A", "a+b,c-"]), Err("bad action `c-`: c is not a live process".to_string()));
+        assert!(fork(&["-A", "a+b,,b-"]).is_err());
+        assert!(fork(&["-S", "-R"]).is_err());
+    }
+
+    #[test]
+    fn test_generator_prints_the_program() {
+        let args = GeneratorArgs::try_parse_from(["generator", "-A", "fork b,wait"]).unwrap();
+        let mut out = Vec::new();
+        cli::generator(&args.options, 0, true, &mut out).unwrap();
+
+        let program = String::from_utf8(out).unwrap();
+        assert!(program.starts_with("// actions: fork b(),wait"), "{}", program);
+    }
+}
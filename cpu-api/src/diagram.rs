@@ -0,0 +1,267 @@
+//! Graphviz DOT and Mermaid diagrams of [`Forker`] trees: the `dot` and
+//! `mermaid` print styles.
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use crate::fork::Forker;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Format {
+    Dot,
+    Mermaid,
+}
+
+impl Format {
+    pub(crate) fn from_style(style: &str) -> Option<Self> {
+        match style {
+            "dot" => Some(Format::Dot),
+            "mermaid" => Some(Format::Mermaid),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Node {
+    Live,
+    // live, with the program it exec'd in this step
+    Execed,
+    Zombie,
+    // left the tree in this step
+    Gone,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Edge {
+    Child,
+    // an orphan which moved to a new parent in this step
+    Reparented,
+    // from the parent of a process which left the tree in this step
+    Gone,
+}
+
+// the tree at one step, with what changed in it; nodes are a process, its
+// label and its state
+struct Frame {
+    label: String,
+    nodes: Vec<(String, String, Node)>,
+    edges: Vec<(String, String, Edge)>,
+    // the program of every node which has one
+    programs: HashMap<String, String>,
+}
+
+impl Frame {
+    fn of(forker: &Forker, label: &str) -> Self {
+        let live = forker.processes().iter().map(|p| (p.clone(), forker.label(p), Node::Live));
+        let zombies = forker.zombies().iter().map(|p| (p.clone(), forker.label(p), Node::Zombie));
+        let nodes: Vec<(String, String, Node)> = live.chain(zombies).collect();
+
+        let mut edges = Vec::new();
+        for (p, _, _) in nodes.iter() {
+            for c in forker.children(p) {
+                edges.push((p.clone(), c.clone(), Edge::Child));
+            }
+        }
+
+        let programs = nodes
+            .iter()
+            .filter_map(|(p, _, _)| forker.program(p).map(|program| (p.clone(), program.to_string())))
+            .collect();
+
+        Frame {
+            label: label.to_string(),
+            nodes,
+            edges,
+            programs,
+        }
+    }
+
+    // `self` after `before`: processes which left are kept, marked as
+    // gone, children which changed parent are marked as reparented and
+    // processes which changed program as exec'd
+    fn changed_from(mut self, before: &Frame) -> Self {
+        // what was alive before, leaving out what exited in the step before
+        let parent_before: HashMap<&str, &str> = before
+            .edges
+            .iter()
+            .filter(|(_, _, edge)| *edge != Edge::Gone)
+            .map(|(p, c, _)| (c.as_str(), p.as_str()))
+            .collect();
+
+        for (p, c, edge) in self.edges.iter_mut() {
+            if parent_before.get(c.as_str()).is_some_and(|old| old != p) {
+                *edge = Edge::Reparented;
+            }
+        }
+
+        for (name, _, node) in self.nodes.iter_mut() {
+            let was_there = before.nodes.iter().any(|(n, _, node)| n == name && *node != Node::Gone);
+            if *node == Node::Live && was_there && before.programs.get(name) != self.programs.get(name) {
+                *node = Node::Execed;
+            }
+        }
+
+        for (name, label, _) in before.nodes.iter().filter(|(_, _, node)| *node != Node::Gone) {
+            if !self.nodes.iter().any(|(n, _, _)| n == name) {
+                self.nodes.push((name.clone(), label.clone(), Node::Gone));
+                if let Some(parent) = parent_before.get(name.as_str()) {
+                    self.edges.push((parent.to_string(), name.clone(), Edge::Gone));
+                }
+            }
+        }
+        self
+    }
+}
+
+// node ids stay the same for a process across steps, and are plain
+// identifiers whatever the process is called
+struct Ids(Vec<String>);
+
+impl Ids {
+    fn get(&mut self, step: usize, name: &str) -> String {
+        let index = match self.0.iter().position(|n| n == name) {
+            Some(index) => index,
+            None => {
+                self.0.push(name.to_string());
+                self.0.len() - 1
+            },
+        };
+        format!("s{}_{}", step, index)
+    }
+}
+
+fn dot_quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn mermaid_quote(s: &str) -> String {
+    let s = s.replace('"', "#quot;").replace('<', "#lt;").replace('>', "#gt;");
+    format!("\"{}\"", s)
+}
+
+fn write_frames<W: Write>(format: Format, frames: &[Frame], clustered: bool, out: &mut W) -> io::Result<()> {
+    let mut ids = Ids(Vec::new());
+    let indent = if clustered { "        " } else { "    " };
+
+    match format {
+        Format::Dot => writeln!(out, "digraph forks {{")?,
+        Format::Mermaid => writeln!(out, "flowchart TD")?,
+    }
+
+    for (step, frame) in frames.iter().enumerate() {
+        if clustered {
+            match format {
+                Format::Dot => {
+                    writeln!(out, "    subgraph cluster_{} {{", step)?;
+                    writeln!(out, "        label={};", dot_quote(&frame.label))?;
+                },
+                Format::Mermaid => writeln!(out, "    subgraph step{} [{}]", step, mermaid_quote(&frame.label))?,
+            }
+        }
+
+        for (name, label, node) in frame.nodes.iter() {
+            let id = ids.get(step, name);
+            match (format, node) {
+                (Format::Dot, Node::Live) => writeln!(out, "{}{} [label={}];", indent, id, dot_quote(label))?,
+                (Format::Dot, Node::Execed) => writeln!(
+                    out,
+                    "{}{} [label={}, style=bold, color=blue];",
+                    indent,
+                    id,
+                    dot_quote(label)
+                )?,
+                (Format::Dot, Node::Zombie) => writeln!(
+                    out,
+                    "{}{} [label={}, style=dotted, color=red];",
+                    indent,
+                    id,
+                    dot_quote(&format!("{} <defunct>", label))
+                )?,
+                (Format::Dot, Node::Gone) => writeln!(
+                    out,
+                    "{}{} [label={}, style=dashed, color=gray, fontcolor=gray];",
+                    indent,
+                    id,
+                    dot_quote(label)
+                )?,
+                (Format::Mermaid, Node::Live) => writeln!(out, "{}{}[{}]", indent, id, mermaid_quote(label))?,
+                (Format::Mermaid, Node::Execed) => writeln!(out, "{}{}[{}]:::execed", indent, id, mermaid_quote(label))?,
+                (Format::Mermaid, Node::Zombie) => writeln!(
+                    out,
+                    "{}{}[{}]:::zombie",
+                    indent,
+                    id,
+                    mermaid_quote(&format!("{} <defunct>", label))
+                )?,
+                (Format::Mermaid, Node::Gone) => writeln!(out, "{}{}[{}]:::exited", indent, id, mermaid_quote(label))?,
+            }
+        }
+
+        for (p, c, edge) in frame.edges.iter() {
+            let (from, to) = (ids.get(step, p), ids.get(step, c));
+            match (format, edge) {
+                (Format::Dot, Edge::Child) => writeln!(out, "{}{} -> {};", indent, from, to)?,
+                (Format::Dot, Edge::Reparented) => writeln!(
+                    out,
+                    "{}{} -> {} [color=red, style=bold, label=\"reparented\"];",
+                    indent, from, to
+                )?,
+                (Format::Dot, Edge::Gone) => {
+                    writeln!(out, "{}{} -> {} [style=dashed, color=gray];", indent, from, to)?
+                },
+                (Format::Mermaid, Edge::Child) => writeln!(out, "{}{} --> {}", indent, from, to)?,
+                (Format::Mermaid, Edge::Reparented) => writeln!(out, "{}{} ==>|reparented| {}", indent, from, to)?,
+                (Format::Mermaid, Edge::Gone) => writeln!(out, "{}{} -.- {}", indent, from, to)?,
+            }
+        }
+
+        if clustered {
+            match format {
+                Format::Dot => writeln!(out, "    }}")?,
+                Format::Mermaid => writeln!(out, "    end")?,
+            }
+        }
+    }
+
+    match format {
+        Format::Dot => writeln!(out, "}}"),
+        Format::Mermaid => {
+            let has = |kind: Node| frames.iter().any(|f| f.nodes.iter().any(|(_, _, node)| *node == kind));
+            if has(Node::Gone) {
+                writeln!(out, "    classDef exited stroke-dasharray: 5 5,color:#999")?;
+            }
+            if has(Node::Zombie) {
+                writeln!(out, "    classDef zombie stroke-dasharray: 2 2,stroke:#c00")?;
+            }
+            if has(Node::Execed) {
+                writeln!(out, "    classDef execed stroke-width:3px,stroke:#00c")?;
+            }
+            Ok(())
+        },
+    }
+}
+
+// the current tree of `forker` as one diagram
+pub(crate) fn write_tree<W: Write>(forker: &Forker, format: Format, out: &mut W) -> io::Result<()> {
+    write_frames(format, &[Frame::of(forker, "")], false, out)
+}
+
+// the tree before and after each of `actions`, one subgraph per step, as
+// `forker` carries them out
+pub(crate) fn write_history<W: Write>(
+    forker: &mut Forker,
+    actions: &[String],
+    format: Format,
+    out: &mut W,
+) -> Result<(), String> {
+    let mut frames = vec![Frame::of(forker, "initial")];
+
+    for a in actions {
+        let label = forker.apply(a)?;
+        let frame = Frame::of(forker, &label).changed_from(frames.last().unwrap());
+        frames.push(frame);
+    }
+
+    write_frames(format, &frames, true, out).map_err(|e| format!("Failed to write trees: {}", e))
+}
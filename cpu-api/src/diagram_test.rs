@@ -0,0 +1,151 @@
+#[cfg(test)]
+mod tests {
+    use crate::fork::Forker;
+
+    fn forker(actions: &str, style: &str, local_reparent: bool) -> Forker {
+        Forker::builder()
+            .action_list(actions.split(',').map(str::to_string).collect())
+            .print_style(style)
+            .local_reparent(local_reparent)
+            .build()
+    }
+
+    fn history(actions: &str, style: &str, local_reparent: bool) -> String {
+        let mut out = Vec::new();
+        forker(actions, style, local_reparent).run(&mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    // the lines of the subgraph of one step
+    fn step<'a>(diagram: &'a str, opening: &str, closing: &str) -> Vec<&'a str> {
+        diagram
+            .lines()
+            .skip_while(|line| !line.starts_with(opening))
+            .skip(1)
+            .take_while(|line| line.trim() != closing)
+            .map(str::trim)
+            .collect()
+    }
+
+    #[test]
+    fn test_dot_tree() {
+        let mut forker = forker("a+b,a+c,b+d", "dot", false);
+        forker.run(&mut std::io::sink()).unwrap();
+
+        assert_eq!(
+            forker.tree().unwrap(),
+            "digraph forks {\n    s0_0 [label=\"a\"];\n    s0_1 [label=\"b\"];\n    s0_2 [label=\"c\"];\n    \
+             s0_3 [label=\"d\"];\n    s0_0 -> s0_1;\n    s0_0 -> s0_2;\n    s0_1 -> s0_3;\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_mermaid_tree() {
+        let mut forker = forker("a+b,b+c", "mermaid", false);
+        forker.run(&mut std::io::sink()).unwrap();
+
+        assert_eq!(
+            forker.tree().unwrap(),
+            "flowchart TD\n    s0_0[\"a\"]\n    s0_1[\"b\"]\n    s0_2[\"c\"]\n    s0_0 --> s0_1\n    s0_1 --> s0_2\n"
+        );
+    }
+
+    #[test]
+    fn test_dot_history_marks_exits_and_reparents() {
+        let diagram = history("a+b,b+c,c+d,b-,a+e", "dot", false);
+
+        assert!(diagram.starts_with("digraph forks {\n    subgraph cluster_0 {\n        label=\"initial\";\n"));
+        assert!(diagram.ends_with("    }\n}\n"));
+        assert_eq!(diagram.matches("subgraph cluster_").count(), 6);
+
+        assert_eq!(
+            step(&diagram, "    subgraph cluster_4 {", "}"),
+            [
+                "label=\"b EXITS\";",
+                "s4_0 [label=\"a\"];",
+                "s4_2 [label=\"c\"];",
+                "s4_3 [label=\"d\"];",
+                "s4_1 [label=\"b\", style=dashed, color=gray, fontcolor=gray];",
+                "s4_0 -> s4_2 [color=red, style=bold, label=\"reparented\"];",
+                "s4_0 -> s4_3 [color=red, style=bold, label=\"reparented\"];",
+                "s4_0 -> s4_1 [style=dashed, color=gray];",
+            ]
+        );
+
+        // the step after shows the exited process no more, and the moved
+        // children as plain children
+        let after = step(&diagram, "    subgraph cluster_5 {", "}");
+        assert!(!after.iter().any(|line| line.contains("\"b\"") || line.contains("dashed")));
+        assert!(after.contains(&"s5_0 -> s5_2;"));
+    }
+
+    #[test]
+    fn test_mermaid_history_with_local_reparent() {
+        let diagram = history("a+b,b+c,c+d,c-", "mermaid", true);
+
+        assert!(diagram.starts_with("flowchart TD\n    subgraph step0 [\"initial\"]\n"));
+        assert!(diagram.ends_with("    end\n    classDef exited stroke-dasharray: 5 5,color:#999\n"));
+        assert_eq!(
+            step(&diagram, "    subgraph step4", "end"),
+            ["s4_0[\"a\"]", "s4_1[\"b\"]", "s4_3[\"d\"]", "s4_2[\"c\"]:::exited", "s4_0 --> s4_1", "s4_1 ==>|reparented| s4_3", "s4_1 -.- s4_2"]
+        );
+
+        // no exits, no class for them
+        assert!(!history("a+b", "mermaid", false).contains("classDef"));
+    }
+
+    #[test]
+    fn test_names_are_quoted() {
+        let dot = history("a+\"x\\", "dot", false);
+        assert!(dot.contains("s1_1 [label=\"\\\"x\\\\\"];"), "{}", dot);
+
+        let mermaid = history("a+\"x", "mermaid", false);
+        assert!(mermaid.contains("s1_1[\"#quot;x\"]"), "{}", mermaid);
+    }
+
+    #[test]
+    fn test_zombies_are_marked() {
+        let mut forker = Forker::builder()
+            .action_list(vec!["a+b".into(), "b-".into(), "a*".into()])
+            .print_style("dot")
+            .zombies(true)
+            .build();
+        let mut out = Vec::new();
+        forker.run(&mut out).unwrap();
+        let diagram = String::from_utf8(out).unwrap();
+
+        assert!(diagram.contains("s2_1 [label=\"b <defunct>\", style=dotted, color=red];"), "{}", diagram);
+        // reaped in the wait
+        assert!(diagram.contains("s3_1 [label=\"b\", style=dashed, color=gray, fontcolor=gray];"));
+
+        let mut forker = Forker::builder()
+            .action_list(vec!["a+b".into(), "b-".into()])
+            .print_style("mermaid")
+            .zombies(true)
+            .build();
+        forker.run(&mut std::io::sink()).unwrap();
+        assert_eq!(
+            forker.tree().unwrap(),
+            "flowchart TD\n    s0_0[\"a\"]\n    s0_1[\"b #lt;defunct#gt;\"]:::zombie\n    s0_0 --> s0_1\n    \
+             classDef zombie stroke-dasharray: 2 2,stroke:#c00\n"
+        );
+    }
+
+    #[test]
+    fn test_execs_are_marked() {
+        let diagram = history("a+b,b=ls,b+c", "dot", false);
+
+        assert!(diagram.contains("s2_1 [label=\"b (ls)\", style=bold, color=blue];"), "{}", diagram);
+        // only in the step of the exec, and the child forked after it runs ls too
+        assert!(diagram.contains("s3_1 [label=\"b (ls)\"];"), "{}", diagram);
+        assert!(diagram.contains("s3_2 [label=\"c (ls)\"];"), "{}", diagram);
+
+        let diagram = history("a+b,b=ls", "mermaid", false);
+        assert!(diagram.contains("s2_1[\"b (ls)\"]:::execed\n"), "{}", diagram);
+        assert!(diagram.ends_with("    classDef execed stroke-width:3px,stroke:#00c\n"), "{}", diagram);
+
+        // becoming a subreaper changes the label but not the program
+        let diagram = history("a+b,b^", "dot", false);
+        assert!(diagram.contains("s2_1 [label=\"b [subreaper]\"];"), "{}", diagram);
+    }
+}
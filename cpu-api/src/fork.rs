@@ -1,8 +1,15 @@
+//! The process tree model behind `fork.py`.
+
 use std::collections::HashMap;
+use std::io::{self, Write};
 use std::string::String;
 
-use crate::hash_map;
-use crate::seed;
+use ostep_rng::Random;
+
+use crate::diagram::{self, Format};
+
+/// The characters which mark actions, and so cannot be in process names.
+pub const ACTION_MARKS: [char; 8] = ['+', '-', '*', '^', '=', '#', '!', '%'];
 
 // from this sort of things:
 // a forks b
@@ -20,10 +27,18 @@ use crate::seed;
 //    |
 //    |- d --- g
 
-// TODO : add `seed` to Forker, for enabled to use `clap::Parser`
+/// Random or scripted forks and exits of a process tree, printed as
+/// `fork.py` does.
+///
+/// ```
+/// use cpu_api::Forker;
+///
+/// let forker = Forker::builder().fork_percentage(0.7).max_actions(5).build();
+/// assert_eq!(forker.processes(), ["a"]);
+/// ```
 #[derive(Clone, Debug, PartialEq)]
-struct Forker {
-    fork_percentage: i32,
+pub struct Forker {
+    fork_percentage: f64,
     max_actions: usize,
     action_list: Vec<String>,
     show_tree: bool,
@@ -32,369 +47,827 @@ struct Forker {
     local_reparent: bool,
     print_style: String,
     solve: bool,
-    root_name: char,
+    zombies: bool,
+    subreaper: bool,
+    kill_descendants: bool,
+    root_name: String,
 
     // process list: names of all active processes
-    process_list: Vec<char>,
+    process_list: Vec<String>,
+
+    // processes which exited and wait in the tree for their parent to reap
+    // them, and the orphans the root took in, which it reaps on their exit
+    defunct: Vec<String>,
+    adopted: Vec<String>,
+
+    // processes blocked in `X*` until one of their children exits
+    blocked: Vec<String>,
+
+    // processes which take in the orphans below them, as
+    // `prctl(PR_SET_CHILD_SUBREAPER)` asks
+    subreapers: Vec<String>,
+
+    // the process group of each process, named after the process which
+    // started it; forks inherit it
+    groups: HashMap<String, String>,
+
+    // the program each process runs since its last exec, if it has exec'd
+    // or was forked by one which had
+    programs: HashMap<String, String>,
 
     // for each process, it has a list of its children
-    children: HashMap<char, Vec<char>>,
+    children: HashMap<String, Vec<String>>,
 
     // track parents
-    parents: HashMap<char, char>,
+    parents: HashMap<String, String>,
 
-    // process names
-    name_length: u32,
-    base_names: String,
+    // process names: a, b, ..., Z, then aa, ab, ..., ZZ, then aaa, ...
+    base_names: Vec<String>,
 
-    curr_names: String,
+    curr_names: Vec<String>,
     curr_index: usize,
+
+    // draws the random actions
+    rng: Random,
 }
 
-impl Forker {
-    fn new(
-        fork_percentage: i32,
-        max_actions: usize,
-        action_list: Vec<String>,
-        show_tree: bool,
-        just_final: bool,
-        leaf_only: bool,
-        local_reparent: bool,
-        print_style: String,
-        solve: bool,
-    ) -> Self {
-        // root name must set to "a"
-        let root_name = 'a';
-        let process_list = vec![root_name];
-        let children = hash_map! { root_name => vec![] };
+/// Options of a [`Forker`], with the defaults of `fork.py`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ForkerBuilder {
+    fork_percentage: f64,
+    max_actions: usize,
+    action_list: Vec<String>,
+    show_tree: bool,
+    just_final: bool,
+    leaf_only: bool,
+    local_reparent: bool,
+    print_style: String,
+    solve: bool,
+    zombies: bool,
+    subreaper: bool,
+    kill_descendants: bool,
+    root: String,
+    rng: Random,
+}
+
+impl Default for ForkerBuilder {
+    fn default() -> Self {
+        Self {
+            fork_percentage: 0.7,
+            max_actions: 5,
+            action_list: Vec::new(),
+            show_tree: false,
+            just_final: false,
+            leaf_only: false,
+            local_reparent: false,
+            print_style: "fancy".to_string(),
+            solve: false,
+            zombies: false,
+            subreaper: false,
+            kill_descendants: false,
+            root: "a".to_string(),
+            rng: Random::default(),
+        }
+    }
+}
+
+impl ForkerBuilder {
+    /// Chance, from 0 to 1, that a random action is a fork rather than an
+    /// exit. Defaults to 0.7.
+    pub fn fork_percentage(mut self, fork_percentage: f64) -> Self {
+        self.fork_percentage = fork_percentage;
+        self
+    }
+
+    /// Number of random actions to generate. Defaults to 5.
+    pub fn max_actions(mut self, max_actions: usize) -> Self {
+        self.max_actions = max_actions;
+        self
+    }
+
+    /// Actions to run instead of random ones, each `X+Y` (X forks Y), `X-`
+    /// (X exits), `X*` (X waits for a child), `X^` (X becomes a
+    /// subreaper), `X=P` (X execs program P), `X#` (X starts its own process
+    /// group), `X!` (X is killed) or `X%` (process group X is killed).
+    pub fn action_list(mut self, action_list: Vec<String>) -> Self {
+        self.action_list = action_list;
+        self
+    }
+
+    /// Show the trees and ask for the actions, instead of the other way
+    /// round.
+    pub fn show_tree(mut self, show_tree: bool) -> Self {
+        self.show_tree = show_tree;
+        self
+    }
+
+    /// Only show the final tree instead of one after every action.
+    pub fn just_final(mut self, just_final: bool) -> Self {
+        self.just_final = just_final;
+        self
+    }
+
+    /// Only let processes without children exit.
+    pub fn leaf_only(mut self, leaf_only: bool) -> Self {
+        self.leaf_only = leaf_only;
+        self
+    }
+
+    /// Give the orphans of an exiting process to its parent instead of to
+    /// the root.
+    pub fn local_reparent(mut self, local_reparent: bool) -> Self {
+        self.local_reparent = local_reparent;
+        self
+    }
+
+    /// How to draw trees: `basic`, `line1`, `line2` or `fancy` (the default)
+    /// as text, or `dot` (Graphviz) or `mermaid` as a diagram. With a
+    /// diagram style, [`Forker::run`] draws every step in one diagram,
+    /// answers included.
+    pub fn print_style(mut self, print_style: &str) -> Self {
+        self.print_style = print_style.to_string();
+        self
+    }
+
+    /// Print the answers too.
+    pub fn solve(mut self, solve: bool) -> Self {
+        self.solve = solve;
+        self
+    }
+
+    /// Keep exited processes in the tree as zombies until their parent
+    /// waits for them with `X*`, as `wait()` does; a wait with only live
+    /// children blocks until the next one exits. The root reaps the
+    /// orphans it adopts by itself, as `init` does. Without it, exits
+    /// leave the tree at once, as in `fork.py`.
+    pub fn zombies(mut self, zombies: bool) -> Self {
+        self.zombies = zombies;
+        self
+    }
+
+    /// Reparent orphans as Linux does: the children of an exiting process,
+    /// but not their own children, go to its nearest ancestor which became
+    /// a child subreaper with `X^`, or to the root if there is none. The
+    /// other modes keep the `X^` marks but ignore them. Takes over from
+    /// [`ForkerBuilder::local_reparent`].
+    pub fn subreaper(mut self, subreaper: bool) -> Self {
+        self.subreaper = subreaper;
+        self
+    }
+
+    /// Kill the descendants of killed processes too, so that `X!` kills the
+    /// subtree of X. Without it they are orphans, reparented as on an exit.
+    pub fn kill_descendants(mut self, kill_descendants: bool) -> Self {
+        self.kill_descendants = kill_descendants;
+        self
+    }
+
+    /// Name of the root process. Defaults to `a`, as in `fork.py`.
+    pub fn root(mut self, root: &str) -> Self {
+        self.root = root.to_string();
+        self
+    }
+
+    /// The random stream to draw the random actions from. Defaults to seed 0.
+    pub fn rng(mut self, rng: Random) -> Self {
+        self.rng = rng;
+        self
+    }
+
+    /// A forker with these options and just the root process.
+    pub fn build(self) -> Forker {
+        let root_name = self.root;
+        let process_list = vec![root_name.clone()];
+        let children = hash_map! { root_name.clone() => vec![] };
         let parents = HashMap::new();
-        let base_names = r#"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ"#.to_string();
+        let groups = hash_map! { root_name.clone() => root_name.clone() };
+        let base_names: Vec<String> = ('a'..='z')
+            .chain('A'..='Z')
+            .map(|name| name.to_string())
+            .collect();
         let curr_name = base_names.clone();
 
-        Self {
-            fork_percentage,
-            max_actions,
-            action_list,
-            show_tree,
-            just_final,
-            leaf_only,
-            local_reparent,
-            print_style,
-            solve,
+        Forker {
+            fork_percentage: self.fork_percentage,
+            max_actions: self.max_actions,
+            action_list: self.action_list,
+            show_tree: self.show_tree,
+            just_final: self.just_final,
+            leaf_only: self.leaf_only,
+            local_reparent: self.local_reparent,
+            print_style: self.print_style,
+            solve: self.solve,
+            zombies: self.zombies,
+            subreaper: self.subreaper,
+            kill_descendants: self.kill_descendants,
             root_name,
             process_list,
+            defunct: Vec::new(),
+            adopted: Vec::new(),
+            blocked: Vec::new(),
+            subreapers: Vec::new(),
+            groups,
+            programs: HashMap::new(),
             children,
             parents,
-            name_length: 1,
             base_names,
             curr_names: curr_name,
             curr_index: 1,
+            rng: self.rng,
         }
     }
+}
+
+impl Forker {
+    /// Options for a new forker.
+    pub fn builder() -> ForkerBuilder {
+        ForkerBuilder::default()
+    }
+
+    /// Names of the live processes, in the order they were created.
+    pub fn processes(&self) -> &[String] {
+        &self.process_list
+    }
+
+    /// Names of the zombies: processes which exited but which their parent
+    /// has not waited for yet, oldest exit first.
+    pub fn zombies(&self) -> &[String] {
+        &self.defunct
+    }
+
+    /// Children of process `p`, oldest first, zombies included.
+    pub fn children(&self, p: &str) -> &[String] {
+        self.children.get(p).map_or(&[], |c| c.as_slice())
+    }
+
+    /// Parent of process `p`, live or zombie; the root has none.
+    pub fn parent(&self, p: &str) -> Option<&str> {
+        self.parents
+            .get(p)
+            .map(String::as_str)
+            .filter(|_| self.is_live(p) || self.is_zombie(p))
+    }
+
+    /// Program process `p` runs: the last one it exec'd, or the one of the
+    /// parent which forked it; `None` for the image of the root.
+    pub fn program(&self, p: &str) -> Option<&str> {
+        self.programs.get(p).map(String::as_str)
+    }
+
+    /// Process group of process `p`, live or zombie: the name of the process
+    /// which started it.
+    pub fn group(&self, p: &str) -> Option<&str> {
+        self.groups.get(p).map(String::as_str)
+    }
+
+    /// Whether `p` takes in the orphans below it.
+    pub fn is_subreaper(&self, p: &str) -> bool {
+        self.subreapers.iter().any(|x| x == p)
+    }
+
+    // `p`, followed by its program in brackets if it has one, and marked if
+    // it is a subreaper or out of the group of the root
+    pub(crate) fn label(&self, p: &str) -> String {
+        let mut label = p.to_string();
+        if let Some(program) = self.program(p) {
+            label.push_str(&format!(" ({})", program));
+        }
+        if self.is_subreaper(p) {
+            label.push_str(" [subreaper]");
+        }
+        match self.group(p) {
+            Some(group) if group != self.groups[&self.root_name] => label.push_str(&format!(" [group {}]", group)),
+            _ => {},
+        }
+        label
+    }
+
+    /// Whether `p` has exited but is still in the tree.
+    pub fn is_zombie(&self, p: &str) -> bool {
+        self.defunct.iter().any(|x| x == p)
+    }
+
+    /// Whether `p` is blocked in a wait (`X*`) until one of its children
+    /// exits; it cannot act until then.
+    pub fn is_blocked(&self, p: &str) -> bool {
+        self.blocked.iter().any(|x| x == p)
+    }
 
     fn grow_names(&mut self) {
-        let mut new_names: Vec<char> = Vec::new();
-        for b1 in self.curr_names.chars() {
-            for b2 in self.base_names.chars() {
-                new_names.push(format!("{}{}", b1, b2).chars().next().unwrap());
+        let mut new_names: Vec<String> = Vec::new();
+        for b1 in self.curr_names.iter() {
+            for b2 in self.base_names.iter() {
+                new_names.push(format!("{}{}", b1, b2));
             }
         }
 
-        self.curr_names = new_names.iter().collect();
+        self.curr_names = new_names;
         self.curr_index = 0;
     }
 
-    fn get_name(&mut self) -> char {
+    fn get_name(&mut self) -> String {
         if self.curr_index == self.curr_names.len() {
             self.grow_names();
         }
 
-        let name = self
-            .curr_names
-            .chars()
-            .nth(self.curr_index as usize)
-            .unwrap();
-
+        let name = self.curr_names[self.curr_index].clone();
         self.curr_index += 1;
 
         name
     }
 
-    fn walk(&self, p: char, level: usize, pmask: &mut [bool], is_last: bool) {
-        let chars: [&str; 4];
+    fn is_live(&self, p: &str) -> bool {
+        self.process_list.iter().any(|x| x == p)
+    }
+
+    // the glyphs for a vertical, a horizontal, a middle child and the
+    // last child; `None` for the basic style, which only indents
+    fn style_chars(&self) -> Result<Option<[&'static str; 4]>, String> {
         match self.print_style.as_str() {
-            "basic" => {
-                for _ in 0..level {
-                    print!("    ");
-                }
+            "basic" => Ok(None),
+            "line1" => Ok(Some(["|", "-", "+", "|"])),
+            "line2" => Ok(Some(["|", "_", "|", "|"])),
+            "fancy" => Ok(Some(["│", "─", "├", "└"])),
+            _ => Err(format!("bad style {}", self.print_style)),
+        }
+    }
 
-                println!("{}", p);
+    /// Write the current tree in the chosen style to `out`, one process
+    /// per line, or as a diagram.
+    ///
+    /// # Errors
+    ///
+    /// Fails on an unknown print style and when `out` fails.
+    pub fn write_tree<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        if let Some(format) = Format::from_style(&self.print_style) {
+            return diagram::write_tree(self, format, out);
+        }
 
-                for child in self.children[&p].iter() {
-                    self.walk(*child, level + 1, pmask, false);
-                }
+        let chars = self
+            .style_chars()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+
+        // depth first with a stack rather than recursion, so that trees may
+        // be as deep as they like; `last[l]` tells whether the ancestor at
+        // level l is the last of its siblings, which ends its vertical
+        let mut last: Vec<bool> = Vec::new();
+        let mut stack = vec![(self.root_name.as_str(), 0, true)];
+
+        while let Some((p, level, is_last)) = stack.pop() {
+            last.truncate(level);
+            last.push(is_last);
+
+            match chars {
+                None => write!(out, "{:1$}", "", 4 * level)?,
+                Some(chars) if level > 0 => {
+                    for &done in last[1..level].iter() {
+                        match done {
+                            true => write!(out, "    ")?,
+                            false => write!(out, "{}   ", chars[0])?,
+                        }
+                    }
+                    let branch = if is_last { chars[3] } else { chars[2] };
+                    write!(out, "{}{}{} ", branch, chars[1], chars[1])?;
+                },
+                Some(_) => {},
+            }
+            match self.is_zombie(p) {
+                true => writeln!(out, "{} <defunct>", self.label(p))?,
+                false => writeln!(out, "{}", self.label(p))?,
+            }
 
-                return;
+            let children = &self.children[p];
+            for (i, child) in children.iter().enumerate().rev() {
+                stack.push((child, level + 1, i + 1 == children.len()));
             }
+        }
+        Ok(())
+    }
 
-            "line1" => chars = ["|", "-", "+", "|"],
-            "line2" => chars = ["|", "_", "|", "|"],
-            "fancy" => chars = ["│", "─", "├", "└"],
+    /// The current tree in the chosen style, as [`Forker::write_tree`]
+    /// writes it.
+    ///
+    /// # Errors
+    ///
+    /// Fails on an unknown print style.
+    pub fn tree(&self) -> Result<String, String> {
+        let mut out = Vec::new();
+        self.write_tree(&mut out).map_err(|e| e.to_string())?;
+        Ok(String::from_utf8(out).unwrap())
+    }
 
-            _ => {
-                println!("bad style {}", self.print_style);
-                std::process::exit(1);
-            }
+    pub(crate) fn do_fork(&mut self, p: &str, c: &str) -> String {
+        self.process_list.push(c.to_string());
+        self.children.insert(c.to_string(), vec![]);
+        self.children.get_mut(p).unwrap().push(c.to_string());
+        self.parents.insert(c.to_string(), p.to_string());
+
+        // the child starts as a copy of its parent, program and group
+        // included
+        if let Some(program) = self.programs.get(p).cloned() {
+            self.programs.insert(c.to_string(), program);
         }
+        self.groups.insert(c.to_string(), self.groups[p].clone());
 
-        // print something before node
-        if level > 0 {
-            // main printing
-            for i in 0..level - 1 {
-                match pmask[i] {
-                    true => print!("{}   ", chars[0]),
-                    false => print!("    "),
-                }
+        format!("{} forks {}", p, c)
+    }
+
+    // a new program in the same process: its name, parent and children stay
+    pub(crate) fn do_exec(&mut self, p: &str, program: &str) -> String {
+        self.programs.insert(p.to_string(), program.to_string());
+
+        format!("{} EXECS {}", p, program)
+    }
+
+    fn collect_children(&self, p: &str) -> Vec<String> {
+        let mut l = vec![p.to_string()];
+        for c in self.children[p].iter() {
+            l.extend(self.collect_children(c));
+        }
+        l
+    }
+
+    fn do_exit(&mut self, p: &str) -> String {
+        // remove the process from the process list
+        let exit_parent = self.parents[p].clone();
+        self.process_list.retain(|x| x != p);
+        self.blocked.retain(|x| x != p);
+
+        // for each orphan, set its parent to exiting proc's parent or root,
+        // or the nearest subreaper above it
+        if self.subreaper {
+            let reaper = self.nearest_subreaper(p);
+            for orphan in self.children[p].clone() {
+                self.adopt(&reaper, orphan);
             }
-            // "|__"
-            match pmask[level - 1] {
-                true => print!("{}{}{} ", chars[3], chars[1], chars[1]),
-                false => print!("{}{}{} ", chars[2], chars[1], chars[1]),
+        } else if self.local_reparent {
+            for orphan in self.children[p].clone() {
+                self.adopt(&exit_parent, orphan);
             }
         } else {
-            // "___"
-            print!(" {} {} ", chars[1], chars[1]);
+            // should set ALL descendants to be child of ROOT
+            let root_name = self.root_name.clone();
+            let desc = self.collect_children(p);
+            for d in desc.into_iter().filter(|d| d != p) {
+                self.children.insert(d.clone(), vec![]);
+                self.adopt(&root_name, d);
+            }
         }
+        self.children.insert(p.to_string(), vec![]);
 
-        // print node
-        println!("{}", p);
-
-        // undo parent verticals
-        if is_last {
-            pmask[level - 1] = false;
+        // a zombie stays in the tree until its parent waits for it, unless
+        // the parent is waiting already
+        if self.zombies && !self.adopted.iter().any(|x| x == p) && !self.is_blocked(&exit_parent) {
+            self.defunct.push(p.to_string());
+            return format!("{} EXITS", p);
         }
 
-        // recurse
-        pmask[level] = true;
+        self.reap(p);
+        self.blocked.retain(|x| *x != exit_parent);
+        match self.zombies {
+            true => format!("{} EXITS (reaped by {})", p, exit_parent),
+            false => format!("{} EXITS", p),
+        }
+    }
 
-        for child in self.children[&p][..self.children[&p].len() - 1].iter() {
-            self.walk(*child, level + 1, pmask, false);
+    // the closest ancestor of `p` which is a subreaper, else the root
+    fn nearest_subreaper(&self, p: &str) -> String {
+        let mut ancestor = &self.parents[p];
+        while *ancestor != self.root_name && !self.is_subreaper(ancestor) {
+            ancestor = &self.parents[ancestor];
         }
+        ancestor.clone()
+    }
 
-        for child in self.children[&p][self.children[&p].len() - 1..].iter() {
-            self.walk(*child, level + 1, pmask, true);
+    // give `orphan` to `parent`; the root reaps zombies it takes in at once,
+    // and so does a parent blocked in a wait
+    fn adopt(&mut self, parent: &str, orphan: String) {
+        self.parents.insert(orphan.clone(), parent.to_string());
+        self.children.get_mut(parent).unwrap().push(orphan.clone());
+
+        if self.is_blocked(parent) && self.is_zombie(&orphan) {
+            self.reap(&orphan);
+            self.blocked.retain(|x| x != parent);
+        } else if parent == self.root_name {
+            match self.is_zombie(&orphan) {
+                true => self.reap(&orphan),
+                false => self.adopted.push(orphan),
+            }
         }
     }
 
-    fn print_tree(&self) {
-        self.walk(self.root_name, 0, &mut [false; 100], false)
+    // take an exited process out of the tree for good
+    fn reap(&mut self, p: &str) {
+        let parent = self.parents.remove(p).unwrap();
+        self.children.get_mut(&parent).unwrap().retain(|x| x != p);
+        self.defunct.retain(|x| x != p);
+        self.adopted.retain(|x| x != p);
+        self.programs.remove(p);
+        self.subreapers.retain(|x| x != p);
+        self.groups.remove(p);
     }
 
-    fn do_fork(&mut self, p: char, c: char) -> String {
-        self.process_list.push(c);
-        self.children.insert(c, vec![]);
-        self.children.get_mut(&p).unwrap().push(c);
-        self.parents.insert(c, p);
+    // live processes in group `g`, oldest first
+    fn group_members(&self, g: &str) -> Vec<String> {
+        self.process_list
+            .iter()
+            .filter(|p| self.groups[*p] == g)
+            .cloned()
+            .collect()
+    }
 
-        format!("forks {} -> {}", p, c)
+    // put `p` in group `g`, which a tree read from elsewhere may need
+    pub(crate) fn set_group(&mut self, p: &str, g: &str) {
+        self.groups.insert(p.to_string(), g.to_string());
     }
 
-    fn collect_children(&self, p: char) -> Vec<char> {
-        match self.children[&p].is_empty() {
-            true => vec![p],
-            false => {
-                let mut l = vec![p];
-                for c in self.children[&p].iter() {
-                    l.extend(self.collect_children(*c));
-                }
-                l
-            }
-        }
+    // make `p` a zombie, which a tree read from elsewhere may hold
+    pub(crate) fn set_defunct(&mut self, p: &str) {
+        self.process_list.retain(|x| x != p);
+        self.defunct.push(p.to_string());
     }
 
-    fn do_exit(&mut self, p: char) -> String {
-        // remove the process from the process list
-        if p == self.root_name {
-            return "root process cannot exit".to_string();
-        }
+    fn do_setpgid(&mut self, p: &str) -> String {
+        self.groups.insert(p.to_string(), p.to_string());
 
-        let exit_parent = self.parents[&p];
-        self.process_list.retain(|&x| x != p);
+        format!("{} starts group {}", p, p)
+    }
 
-        // for each orphan, set its parent to exiting proc's parent or root
-        if self.local_reparent {
-            for orphan in self.children[&p].clone().iter() {
-                self.parents.insert(*orphan, exit_parent);
-                self.children.get_mut(&exit_parent).unwrap().push(*orphan);
-            }
-        } else {
-            // should set ALL descendants to be child of ROOT
-            let desc = self.collect_children(p);
-            for d in desc.iter() {
-                self.children.insert(*d, vec![]);
-                self.parents.insert(*d, self.root_name);
-                self.children.get_mut(&self.root_name).unwrap().push(*d);
+    // kill `targets`, and their descendants with `kill_descendants`; the
+    // root ignores the signal, as `init` does. Gives who was killed,
+    // ancestors first
+    fn kill(&mut self, targets: Vec<String>) -> Vec<String> {
+        let mut killed: Vec<String> = Vec::new();
+        for t in targets {
+            let doomed = match self.kill_descendants {
+                true => self.collect_children(&t),
+                false => vec![t],
+            };
+            for d in doomed {
+                if self.is_live(&d) && d != self.root_name && !killed.contains(&d) {
+                    killed.push(d);
+                }
             }
         }
 
-        // remove the entry from its parent child list
-        self.children
-            .get_mut(&exit_parent)
-            .unwrap()
-            .retain(|&x| x != p);
+        // descendants go first, so that no one is reparented on the way
+        for k in killed.iter().rev() {
+            self.do_exit(k);
+        }
+        killed
+    }
 
-        // should never use this.
-        self.children.insert(p, vec![]);
-        self.parents.insert(p, ' ');
+    fn do_subreaper(&mut self, p: &str) -> String {
+        if !self.is_subreaper(p) {
+            self.subreapers.push(p.to_string());
+        }
+
+        format!("{} becomes a subreaper", p)
+    }
 
-        // remove the entry for this proc from children
-        format!("{} EXITS", p)
+    fn do_wait(&mut self, p: &str) -> String {
+        let zombie = self.children[p].iter().find(|c| self.is_zombie(c)).cloned();
+
+        match zombie {
+            Some(z) => {
+                self.reap(&z);
+                format!("{} waits for {}", p, z)
+            },
+            None if self.children[p].is_empty() => format!("{} waits (fails: no children)", p),
+            None => {
+                self.blocked.push(p.to_string());
+                format!("{} waits (blocks: no child has exited)", p)
+            },
+        }
     }
 
-    fn bad_action(&self, action: String) -> Result<Vec<String>, String> {
+    fn bad_action(&self, action: &str) -> Result<Vec<String>, String> {
         Err(format!(
-            "bad action: {}, must be `X+Y` or `X-` where `X` and `Y` are processes",
+            "bad action: {}, must be `X+Y`, `X-`, `X*`, `X^`, `X=P`, `X#`, `X!` or `X%` where `X` and `Y` are processes and `P` a program",
             action
         ))
     }
 
-    // TODO: should refactor
-    fn is_legal(& self, action: String) -> Result<Vec<String>, String> {
-        if action.contains("+") {
-            let tmp: Vec<&str> = action.split("+").collect();
-            if tmp.len() != 2 {
-                return self.bad_action(action);
-            }
-            return Ok(vec![tmp[0].to_string(), tmp[1].to_string()]);
-        } else if action.contains("-") {
-            let tmp: Vec<&str> = action.split("-").collect();
-            if tmp.len() != 2 {
+    /// Check one action: `X+Y` (X forks Y) gives the names `[X, Y]`, `X-`
+    /// (X exits), `X*` (X waits for a child), `X^` (X becomes a subreaper),
+    /// `X#` (X starts its own process group), `X!` (X is killed) and `X%`
+    /// (group X is killed) give `[X]`, and `X=P` (X execs program P) gives
+    /// `[X, P]`.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the action has another shape or a name is empty.
+    pub fn is_legal(&self, action: &str) -> Result<Vec<String>, String> {
+        // a program may be called anything, `g++` included
+        if let Some((name, program)) = action.split_once('=') {
+            if name.is_empty() || name.contains(ACTION_MARKS) || program.is_empty() || program.contains(',') {
                 return self.bad_action(action);
             }
-            return Ok(vec![tmp[0].to_string()]);
-        } else {
+            return Ok(vec![name.to_string(), program.to_string()]);
+        }
+
+        let names = match (action.split_once('+'), action.strip_suffix(['-', '*', '^', '#', '!', '%'])) {
+            (Some((parent, child)), _) => vec![parent, child],
+            (None, Some(name)) => vec![name],
+            _ => return self.bad_action(action),
+        };
+
+        // process names are a few letters, without the action marks
+        if names.iter().any(|name| name.is_empty() || name.contains(ACTION_MARKS)) {
             return self.bad_action(action);
         }
+
+        Ok(names.iter().map(|name| name.to_string()).collect())
+    }
+
+    /// Draw `max_actions` random actions from the forker's random stream,
+    /// as `fork.py` does: a fork by a random live process with chance
+    /// `fork_percentage`, else an exit of a random live process other than
+    /// the root.
+    pub fn random_actions(&mut self) -> Vec<String> {
+        let mut action_list = Vec::new();
+        let mut actions = 0;
+        let mut temp_process_list = vec![self.root_name.clone()];
+
+        while actions < self.max_actions {
+            match self.rng.random() < self.fork_percentage {
+                true => {
+                    let fork_choice = self.rng.choice(&temp_process_list).unwrap().clone();
+                    let new_child = self.get_name();
+                    action_list.push(format!("{}+{}", fork_choice, new_child));
+                    temp_process_list.push(new_child);
+                },
+                false => {
+                    let exit_choice = self.rng.choice(&temp_process_list).unwrap().clone();
+                    if exit_choice == self.root_name {
+                        continue;
+                    }
+
+                    temp_process_list.retain(|x| *x != exit_choice);
+                    action_list.push(format!("{}-", exit_choice));
+                }
+            }
+            actions += 1;
+        }
+        action_list
     }
 
-    #[warn(unused_variables)]
-    fn run(&mut self) {
-        println!("                           Process Tree:");
-        self.print_tree();
-        println!("");
+    // carry out one action, giving what `fork.py` prints for it
+    pub(crate) fn apply(&mut self, action: &str) -> Result<String, String> {
+        let names = self.is_legal(action)?;
+        let bad = |why: String| Err(format!("bad action `{}`: {}", action, why));
 
-        let mut action_list: Vec<String> = Vec::new();
+        let p = &names[0];
 
-        if self.action_list.is_empty() {
-            let mut action_list = self.action_list
-                .iter()
-                .map(|x| x.to_string())
-                .collect::<Vec<String>>();
-        } else {
-            let mut actions = 0;
-            let mut temp_process_list = vec![self.root_name];
-            let level_list = hash_map!(self.root_name => 1);
-
-            while actions < self.max_actions {
-                match seed::seed() < self.fork_percentage {
-                    true => {
-                        let fork_choice = seed::choice(&temp_process_list);
-                        let new_child = self.get_name();
-                        action_list.push(format!("{}+{}", fork_choice, new_child));
-                        temp_process_list.push(new_child);
-                    },
-                    false => {
-                        let exit_choice = seed::choice(&temp_process_list);
-                        if exit_choice == &self.root_name {
-                            continue;
-                        }
+        // a group outlives the process which started it
+        if action.ends_with('%') {
+            let members = self.group_members(p);
+            if members.is_empty() {
+                return bad(format!("there is no group {}", p));
+            }
+            let killed = self.kill(members);
+            return match killed.is_empty() {
+                true => Ok(format!("group {} is killed: no one dies", p)),
+                false => Ok(format!("group {} is killed: {}", p, killed.join(", "))),
+            };
+        }
 
-                        // this is a hack to get around the borrow checker
-                        // TODO: find more better solution
-                        let mut temp_process_list = temp_process_list.clone();
-                        let index = temp_process_list
-                                    .iter()
-                                    .position(|x| x == exit_choice)
-                                    .unwrap();
+        if !self.is_live(p) {
+            return bad(format!("{} is not a live process", p));
+        }
 
-                        temp_process_list.remove(index);
+        // a blocked process can still be killed, but do nothing itself
+        if self.is_blocked(p) && !action.ends_with('!') {
+            return bad(format!("{} is blocked in a wait until a child exits", p));
+        }
 
-                        action_list.push(format!("{}-", exit_choice));
-                    }
+        match names.get(1) {
+            Some(program) if action.contains('=') => Ok(self.do_exec(p, program)),
+            Some(c) => {
+                if self.is_live(c) {
+                    return bad(format!("{} is already a live process", c));
+                }
+                if self.is_zombie(c) {
+                    return bad(format!("{} is a zombie which has not been waited for", c));
+                }
+                if !self.group_members(c).is_empty() {
+                    return bad(format!("{} is still the id of a process group", c));
+                }
+                Ok(self.do_fork(p, c))
+            },
+            None if action.ends_with('*') => {
+                if !self.zombies {
+                    return bad("waits need zombies, which are off".to_string());
+                }
+                Ok(self.do_wait(p))
+            },
+            None if action.ends_with('^') => Ok(self.do_subreaper(p)),
+            None if action.ends_with('#') => Ok(self.do_setpgid(p)),
+            None if action.ends_with('!') => {
+                if *p == self.root_name {
+                    return bad("the root process cannot be killed".to_string());
                 }
-                actions += 1;
-            }
 
-            let mut action = String::new();
-            for a in action_list.iter() {
-                let tmp = self.is_legal(a.clone()).unwrap();
-                match tmp.len() {
-                    2 =>  {
-                        let fork_choice = tmp[0].chars().next().unwrap();
-                        let new_child = tmp[1].chars().next().unwrap();
-
-                        if !self.process_list.contains(&fork_choice) {
-                            println!("{} is not a valid process", fork_choice);
-                            continue;
-                        }
+                let killed = self.kill(vec![p.to_string()]);
+                match killed.len() {
+                    1 => Ok(format!("{} is killed", p)),
+                    _ => Ok(format!("{} is killed, with {}", p, killed[1..].join(", "))),
+                }
+            },
+            None => {
+                if *p == self.root_name {
+                    return bad("the root process cannot exit".to_string());
+                }
 
-                        action = String::from(self.do_fork(fork_choice, new_child));
-                    },
-                    _ => {
-                        let exit_choice = tmp[0].chars().next().unwrap();
+                // zombie children do not stop a process from exiting
+                if self.leaf_only && self.children[p].iter().any(|c| self.is_live(c)) {
+                    return Ok(format!("{} EXITS (failed: has children)", p));
+                }
+                Ok(self.do_exit(p))
+            },
+        }
+    }
 
-                        if !self.process_list.contains(&exit_choice) {
-                            println!("{} is not a valid process", exit_choice);
-                            continue;
-                        }
+    /// Run the given actions, or random ones if none were given, writing
+    /// the trees and actions (or the questions about them) to `out`.
+    ///
+    /// # Errors
+    ///
+    /// Fails before writing anything if the print style is unknown, or an
+    /// action is malformed, names a process which is not alive at that
+    /// point, forks a live process again or makes the root exit. Fails too
+    /// when `out` fails.
+    pub fn run<W: Write>(&mut self, out: &mut W) -> Result<(), String> {
+        let format = Format::from_style(&self.print_style);
+        if format.is_none() {
+            self.style_chars()?;
+        }
 
-                        if self.leaf_only && self.children[&exit_choice].len() > 0 {
-                            println!("{} EXITS (failed: has children.", exit_choice);
-                            continue;
-                        } else {
-                            action = String::from(self.do_exit(exit_choice));
-                        }
-                    }
+        let action_list = match self.action_list.is_empty() {
+            true => self.random_actions(),
+            false => self.action_list.clone(),
+        };
+
+        // try the actions on a copy first, so a bad one fails up front
+        let mut dry_run = self.clone();
+        for a in action_list.iter() {
+            dry_run.apply(a)?;
+        }
+
+        match format {
+            Some(format) => diagram::write_history(self, &action_list, format, out),
+            None => self
+                .write_run(&action_list, out)
+                .map_err(|e| format!("Failed to write trees: {}", e)),
+        }
+    }
+
+    fn write_run<W: Write>(&mut self, action_list: &[String], out: &mut W) -> io::Result<()> {
+        writeln!(out, "                           Process Tree:")?;
+        self.write_tree(out)?;
+        writeln!(out)?;
+
+        for a in action_list.iter() {
+            let action = self
+                .apply(a)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+
+            if self.show_tree {
+                match self.solve {
+                    true => writeln!(out, "Action: {}", action)?,
+                    false => writeln!(out, "Action?")?,
                 }
 
-                if self.show_tree {
-                    match self.solve {
-                        true => println!("Action: {}", action),
-                        false => print!("Action?"),
-                    }
+                if !self.just_final {
+                    self.write_tree(out)?;
+                }
+            } else {
+                // show actions
+                writeln!(out, "Action: {}", action)?;
 
-                    if !self.just_final {
-                        self.print_tree();
-                    }
-                } else {
-                    // show actions
-                    println!("Action: {}", action);
-
-                    if !self.just_final {
-                        match self.solve {
-                            true => self.print_tree(),
-                            false => print!("process Tree?"),
-                        }
+                if !self.just_final {
+                    match self.solve {
+                        true => self.write_tree(out)?,
+                        false => writeln!(out, "Process Tree?")?,
                     }
                 }
             }
-            if self.just_final {
-                match self.show_tree {
-                    true => {
-                        print!("\n                        Final Process Tree:");
-                        self.print_tree();
-                        print!("");
-                    },
-                    false => {
-                        print!("\n                        Final Process Tree?\n")
-                    }
-                }
+        }
+
+        if self.just_final {
+            match self.show_tree || self.solve {
+                true => {
+                    writeln!(out, "\n                        Final Process Tree:")?;
+                    self.write_tree(out)?;
+                    writeln!(out)?;
+                },
+                false => writeln!(out, "\n                        Final Process Tree?\n")?,
             }
         }
+        Ok(())
     }
 }
 
-#[macro_export]
 macro_rules! hash_map (
     { $($key:expr => $value:expr), + } => {
         {
@@ -405,3 +878,4 @@ macro_rules! hash_map (
         }
     };
 );
+pub(crate) use hash_map;
@@ -0,0 +1,614 @@
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use ostep_rng::Random;
+
+    use crate::fork::Forker;
+
+    fn forker() -> Forker {
+        Forker::builder().build()
+    }
+
+    fn run(actions: &str, local_reparent: bool, leaf_only: bool) -> Result<Forker, String> {
+        let mut forker = Forker::builder()
+            .action_list(actions.split(',').map(str::to_string).collect())
+            .local_reparent(local_reparent)
+            .leaf_only(leaf_only)
+            .build();
+        forker.run(&mut io::sink()).map(|_| forker)
+    }
+
+    #[test]
+    fn test_is_legal_fork_and_exit() {
+        let forker = forker();
+
+        assert_eq!(forker.is_legal("a+b"), Ok(vec!["a".to_string(), "b".to_string()]));
+        assert_eq!(forker.is_legal("b-"), Ok(vec!["b".to_string()]));
+        assert_eq!(forker.is_legal("é+ü"), Ok(vec!["é".to_string(), "ü".to_string()]));
+        assert_eq!(forker.is_legal("Z+aa"), Ok(vec!["Z".to_string(), "aa".to_string()]));
+        assert_eq!(forker.is_legal("abc-"), Ok(vec!["abc".to_string()]));
+        assert_eq!(forker.is_legal("a*"), Ok(vec!["a".to_string()]));
+        assert_eq!(forker.is_legal("b^"), Ok(vec!["b".to_string()]));
+        assert_eq!(forker.is_legal("b#"), Ok(vec!["b".to_string()]));
+        assert_eq!(forker.is_legal("b!"), Ok(vec!["b".to_string()]));
+        assert_eq!(forker.is_legal("b%"), Ok(vec!["b".to_string()]));
+        assert_eq!(forker.is_legal("b=ls"), Ok(vec!["b".to_string(), "ls".to_string()]));
+        assert_eq!(forker.is_legal("b=g++"), Ok(vec!["b".to_string(), "g++".to_string()]));
+    }
+
+    #[test]
+    fn test_is_legal_rejects_bad_actions() {
+        let forker = forker();
+
+        for action in ["", "a", "+", "-", "a+", "+b", "a-b", "a+b+c", "a+-", "-a", "a--", "é", "*", "a**", "a*-", "a+b*", "a^^", "^", "a+b^", "a!!", "a#!", "a+b%", "%"] {
+            assert!(forker.is_legal(action).is_err(), "`{}` is legal", action);
+        }
+        for action in ["=ls", "b=", "a+b=ls", "b-=ls", "b*=ls", "b^=ls", "b!=ls", "b=a,b"] {
+            assert!(forker.is_legal(action).is_err(), "`{}` is legal", action);
+        }
+    }
+
+    #[test]
+    fn test_builder_starts_with_root() {
+        let forker = Forker::builder()
+            .fork_percentage(0.3)
+            .max_actions(10)
+            .print_style("basic")
+            .local_reparent(true)
+            .build();
+
+        assert_eq!(forker.processes(), ["a"]);
+        assert!(forker.children("a").is_empty());
+        assert_eq!(forker.parent("a"), None);
+        assert_eq!(Forker::builder().build(), Forker::builder().fork_percentage(0.7).build());
+    }
+
+    #[test]
+    fn test_random_actions_follow_the_seed() {
+        let actions = |seed: u64| {
+            Forker::builder()
+                .max_actions(30)
+                .rng(Random::new(seed))
+                .build()
+                .random_actions()
+        };
+
+        assert_eq!(actions(5), actions(5));
+        assert_ne!(actions(5), actions(6));
+        assert_eq!(actions(5).len(), 30);
+    }
+
+    #[test]
+    fn test_random_actions_only_use_live_processes() {
+        let mut forker = Forker::builder()
+            .max_actions(40)
+            .fork_percentage(0.5)
+            .rng(Random::new(1))
+            .build();
+        let mut live = vec!["a".to_string()];
+
+        for action in forker.random_actions() {
+            let names = forker.is_legal(&action).unwrap();
+            assert!(live.contains(&names[0]), "{} is not alive for `{}`", names[0], action);
+
+            match names.len() {
+                2 => live.push(names[1].clone()),
+                _ => {
+                    assert_ne!(names[0], "a", "the root exited");
+                    live.retain(|p| *p != names[0]);
+                },
+            }
+        }
+    }
+
+    #[test]
+    fn test_random_actions_match_fork_py() {
+        // `fork.py -s 10` and `fork.py -s 3`
+        let actions = |seed: u64| Forker::builder().rng(Random::python(seed)).build().random_actions();
+
+        assert_eq!(actions(10), ["a+b", "a+c", "b+d", "b-", "c+e"]);
+        assert_eq!(actions(3), ["a+b", "b+c", "a+d", "d+e", "b+f"]);
+    }
+
+    #[test]
+    fn test_run_given_actions() {
+        let forker = run("a+b,b+c,c+d,c+e,a+f", false, false).unwrap();
+
+        assert_eq!(forker.processes(), ["a", "b", "c", "d", "e", "f"]);
+        assert_eq!(forker.children("a"), ["b", "f"]);
+        assert_eq!(forker.children("c"), ["d", "e"]);
+        assert_eq!(forker.parent("e"), Some("c"));
+    }
+
+    #[test]
+    fn test_run_exit_reparents_orphans() {
+        // orphans go to the root, their own children flattened too
+        let forker = run("a+b,b+c,c+d,b-", false, false).unwrap();
+        assert_eq!(forker.processes(), ["a", "c", "d"]);
+        assert_eq!(forker.children("a"), ["c", "d"]);
+        assert!(forker.children("c").is_empty());
+        assert_eq!(forker.parent("b"), None);
+
+        // or to the parent of the exiting process
+        let forker = run("a+b,b+c,c+d,c+e,c-", true, false).unwrap();
+        assert_eq!(forker.children("b"), ["d", "e"]);
+        assert_eq!(forker.parent("d"), Some("b"));
+    }
+
+    #[test]
+    fn test_run_leaf_only_keeps_parents() {
+        let forker = run("a+b,b+c,b-,c-", false, true).unwrap();
+
+        assert_eq!(forker.processes(), ["a", "b"]);
+        assert!(forker.children("b").is_empty());
+    }
+
+    #[test]
+    fn test_run_rejects_illegal_actions() {
+        let cases = [
+            ("a+b,c+d", "bad action `c+d`: c is not a live process"),
+            ("a+b,b-,b+c", "bad action `b+c`: b is not a live process"),
+            ("a+b,b-,b-", "bad action `b-`: b is not a live process"),
+            ("a+b,a+b", "bad action `a+b`: b is already a live process"),
+            ("a+b,a-", "bad action `a-`: the root process cannot exit"),
+            ("a+b,b-,b=ls", "bad action `b=ls`: b is not a live process"),
+            (
+                "a+b,bc",
+                "bad action: bc, must be `X+Y`, `X-`, `X*`, `X^`, `X=P`, `X#`, `X!` or `X%` where `X` and `Y` are processes and \
+                 `P` a program",
+            ),
+        ];
+
+        for (actions, error) in cases {
+            assert_eq!(run(actions, false, false).err().as_deref(), Some(error), "{}", actions);
+        }
+
+        // a name may come back once its process has exited
+        assert!(run("a+b,b-,a+b", false, false).is_ok());
+    }
+
+    #[test]
+    fn test_names_grow_past_the_alphabet() {
+        let mut forker = Forker::builder()
+            .fork_percentage(1.0)
+            .max_actions(60)
+            .build();
+        let actions = forker.random_actions();
+        let children: Vec<&str> = actions.iter().map(|a| a.split_once('+').unwrap().1).collect();
+
+        // a is the root, so b..z and A..Z come first
+        assert_eq!(children[..3], ["b", "c", "d"]);
+        assert_eq!(children[49..54], ["Y", "Z", "aa", "ab", "ac"]);
+    }
+
+    #[test]
+    fn test_large_random_run_keeps_the_tree_whole() {
+        let mut forker = Forker::builder()
+            .max_actions(200)
+            .rng(Random::python(4))
+            .build();
+        forker.run(&mut io::sink()).unwrap();
+
+        let processes = forker.processes();
+        let mut names = processes.to_vec();
+        names.sort();
+        names.dedup();
+        assert_eq!(names.len(), processes.len(), "a name was handed out twice");
+
+        // every live process but the root hangs off a live parent which
+        // lists it exactly once
+        for p in processes.iter().filter(|p| *p != "a") {
+            let parent = forker.parent(p).unwrap();
+            assert!(processes.iter().any(|x| x == parent));
+            assert_eq!(forker.children(parent).iter().filter(|c| *c == p).count(), 1);
+        }
+        let listed: usize = processes.iter().map(|p| forker.children(p).len()).sum();
+        assert_eq!(listed, processes.len() - 1);
+    }
+
+    #[test]
+    fn test_run_multi_character_names() {
+        let forker = run("a+bb,bb+ccc,ccc-", true, false).unwrap();
+
+        assert_eq!(forker.processes(), ["a", "bb"]);
+        assert_eq!(forker.parent("bb"), Some("a"));
+        assert!(forker.children("bb").is_empty());
+    }
+
+    fn tree(actions: &str, style: &str) -> String {
+        let mut forker = Forker::builder()
+            .action_list(actions.split(',').map(str::to_string).collect())
+            .print_style(style)
+            .build();
+        forker.run(&mut io::sink()).unwrap();
+        forker.tree().unwrap()
+    }
+
+    #[test]
+    fn test_tree_styles() {
+        let actions = "a+b,a+c,b+d,b+e,d+f,c+g";
+
+        assert_eq!(
+            tree(actions, "fancy"),
+            "a\n├── b\n│   ├── d\n│   │   └── f\n│   └── e\n└── c\n    └── g\n"
+        );
+        assert_eq!(
+            tree(actions, "line1"),
+            "a\n+-- b\n|   +-- d\n|   |   |-- f\n|   |-- e\n|-- c\n    |-- g\n"
+        );
+        assert_eq!(
+            tree(actions, "line2"),
+            "a\n|__ b\n|   |__ d\n|   |   |__ f\n|   |__ e\n|__ c\n    |__ g\n"
+        );
+        assert_eq!(tree(actions, "basic"), "a\n    b\n        d\n            f\n        e\n    c\n        g\n");
+        assert_eq!(tree("a+b,b-", "fancy"), "a\n");
+    }
+
+    #[test]
+    fn test_tree_has_no_depth_limit() {
+        // a chain of 300 processes, each forked by the one before
+        let mut names = vec!["a".to_string()];
+        let mut forker = Forker::builder().fork_percentage(1.0).max_actions(299).build();
+        names.extend(forker.random_actions().iter().map(|a| a.split_once('+').unwrap().1.to_string()));
+        let actions: Vec<String> = names.windows(2).map(|w| format!("{}+{}", w[0], w[1])).collect();
+
+        let drawn = tree(&actions.join(","), "fancy");
+        let deepest = drawn.lines().last().unwrap();
+        assert_eq!(drawn.lines().count(), 300);
+        assert_eq!(deepest, format!("{}└── {}", "    ".repeat(298), names[299]));
+    }
+
+    #[test]
+    fn test_bad_style_is_an_error() {
+        let mut forker = Forker::builder().print_style("boxes").build();
+
+        assert_eq!(forker.tree(), Err("bad style boxes".to_string()));
+
+        let mut out = Vec::new();
+        assert_eq!(forker.run(&mut out), Err("bad style boxes".to_string()));
+        assert!(out.is_empty());
+    }
+
+    #[test]
+    fn test_run_writes_to_the_sink() {
+        let mut forker = Forker::builder()
+            .action_list(vec!["a+b".to_string(), "b-".to_string()])
+            .solve(true)
+            .build();
+        let mut out = Vec::new();
+        forker.run(&mut out).unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "                           Process Tree:\na\n\nAction: a forks b\na\n└── b\nAction: b EXITS\na\n"
+        );
+    }
+
+    fn zombies(actions: &str, local_reparent: bool) -> Result<(Forker, String), String> {
+        let mut forker = Forker::builder()
+            .action_list(actions.split(',').map(str::to_string).collect())
+            .local_reparent(local_reparent)
+            .zombies(true)
+            .solve(true)
+            .build();
+        let mut out = Vec::new();
+        forker.run(&mut out)?;
+        Ok((forker, String::from_utf8(out).unwrap()))
+    }
+
+    #[test]
+    fn test_zombie_waits_for_its_parent() {
+        let (forker, _) = zombies("a+b,b+c,c-", false).unwrap();
+        assert_eq!(forker.processes(), ["a", "b"]);
+        assert_eq!(forker.zombies(), ["c"]);
+        assert!(forker.is_zombie("c"));
+        assert_eq!(forker.children("b"), ["c"]);
+        assert_eq!(forker.parent("c"), Some("b"));
+        assert_eq!(forker.tree().unwrap(), "a\n└── b\n    └── c <defunct>\n");
+
+        let (forker, out) = zombies("a+b,b+c,c-,b*", false).unwrap();
+        assert!(forker.zombies().is_empty());
+        assert!(forker.children("b").is_empty());
+        assert_eq!(forker.parent("c"), None);
+        assert!(out.contains("Action: b waits for c\n"));
+    }
+
+    #[test]
+    fn test_wait_outcomes() {
+        let (_, out) = zombies("a*,a+b,a*,b-,a+c,a+d,c-,d-,a*,a*,a*", false).unwrap();
+        let actions: Vec<&str> = out.lines().filter(|line| line.starts_with("Action:")).collect();
+
+        assert_eq!(
+            actions,
+            [
+                "Action: a waits (fails: no children)",
+                "Action: a forks b",
+                "Action: a waits (blocks: no child has exited)",
+                "Action: b EXITS (reaped by a)",
+                "Action: a forks c",
+                "Action: a forks d",
+                "Action: c EXITS",
+                "Action: d EXITS",
+                "Action: a waits for c",
+                "Action: a waits for d",
+                "Action: a waits (fails: no children)",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_blocked_wait_reaps_the_next_exit() {
+        let (forker, _) = zombies("a+b,b+c,b*", false).unwrap();
+        assert!(forker.is_blocked("b"));
+
+        // the blocked parent reaps its child as it exits, and goes on
+        let (forker, out) = zombies("a+b,a*,b-", false).unwrap();
+        assert!(out.contains("Action: b EXITS (reaped by a)\na\n"), "{}", out);
+        assert!(forker.zombies().is_empty());
+        assert!(!forker.is_blocked("a"));
+        assert!(zombies("a+b,a*,b-,a+c", false).is_ok());
+
+        // until then it does nothing, but can still be killed
+        assert_eq!(
+            zombies("a+b,a*,a+c", false).err().as_deref(),
+            Some("bad action `a+c`: a is blocked in a wait until a child exits")
+        );
+        assert!(zombies("a+b,b+c,b*,b-", false).is_err());
+        let (forker, _) = zombies("a+b,b+c,b*,b!", false).unwrap();
+        assert_eq!(forker.zombies(), ["b"]);
+        assert!(!forker.is_blocked("b"));
+
+        // an orphan zombie handed to a waiting grandparent is reaped too,
+        // which ends the wait before its parent's exit
+        let (forker, _) = zombies("a+b,b+c,c+d,b+e,d-,b*,c-", true).unwrap();
+        assert_eq!(forker.children("b"), ["c", "e"]);
+        assert_eq!(forker.zombies(), ["c"]);
+        assert!(!forker.is_blocked("b"));
+    }
+
+    #[test]
+    fn test_root_reaps_orphans() {
+        // c is orphaned when b exits, so a reaps it as soon as it exits
+        let (forker, out) = zombies("a+b,b+c,b-,c-", false).unwrap();
+        assert!(out.contains("Action: c EXITS (reaped by a)\n"));
+        assert_eq!(forker.zombies(), ["b"]);
+        assert_eq!(forker.children("a"), ["b"]);
+
+        // zombies orphaned to the root are reaped at once
+        let (forker, _) = zombies("a+b,b+c,c+d,d-,b-", false).unwrap();
+        assert_eq!(forker.zombies(), ["b"]);
+        assert_eq!(forker.processes(), ["a", "c"]);
+
+        // but an orphan given to its grandparent waits for that one
+        let (forker, _) = zombies("a+b,b+c,c+d,d-,c-", true).unwrap();
+        assert_eq!(forker.zombies(), ["d", "c"]);
+        assert_eq!(forker.children("b"), ["c", "d"]);
+        assert_eq!(forker.parent("d"), Some("b"));
+    }
+
+    #[test]
+    fn test_zombie_rules() {
+        let cases = [
+            ("a+b,b-,b+c", "bad action `b+c`: b is not a live process"),
+            ("a+b,b-,b-", "bad action `b-`: b is not a live process"),
+            ("a+b,b-,b*", "bad action `b*`: b is not a live process"),
+            ("a+b,b-,a+b", "bad action `a+b`: b is a zombie which has not been waited for"),
+        ];
+        for (actions, error) in cases {
+            assert_eq!(zombies(actions, false).err().as_deref(), Some(error), "{}", actions);
+        }
+
+        // once reaped, the name is free again
+        assert!(zombies("a+b,b-,a*,a+b", false).is_ok());
+
+        // without zombies there is nothing to wait for
+        assert_eq!(
+            run("a+b,a*", false, false).err().as_deref(),
+            Some("bad action `a*`: waits need zombies, which are off")
+        );
+
+        // zombie children do not count for leaf only exits
+        let mut forker = Forker::builder()
+            .action_list(vec!["a+b".into(), "b+c".into(), "c-".into(), "b-".into()])
+            .zombies(true)
+            .leaf_only(true)
+            .build();
+        forker.run(&mut io::sink()).unwrap();
+        assert_eq!(forker.zombies(), ["b"]);
+        assert_eq!(forker.processes(), ["a"]);
+    }
+
+    #[test]
+    fn test_exec_keeps_the_process() {
+        let mut forker = Forker::builder()
+            .action_list("a+b,b+c,b=ls,b+d,d=wc".split(',').map(str::to_string).collect())
+            .solve(true)
+            .build();
+        let mut out = Vec::new();
+        forker.run(&mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+
+        assert!(out.contains("Action: b EXECS ls\na\n└── b (ls)\n    └── c\n"), "{}", out);
+        assert_eq!(forker.processes(), ["a", "b", "c", "d"]);
+        assert_eq!(forker.parent("b"), Some("a"));
+        assert_eq!(forker.children("b"), ["c", "d"]);
+
+        // c was forked before the exec and d after it, then d exec'd too
+        assert_eq!(forker.program("a"), None);
+        assert_eq!(forker.program("b"), Some("ls"));
+        assert_eq!(forker.program("c"), None);
+        assert_eq!(forker.program("d"), Some("wc"));
+        assert_eq!(forker.tree().unwrap(), "a\n└── b (ls)\n    ├── c\n    └── d (wc)\n");
+    }
+
+    #[test]
+    fn test_exec_image_goes_with_the_process() {
+        // forks copy the program, and a name used again starts afresh
+        let forker = run("a+b,b=sh,b+c,b-,c-,a+b", false, false).unwrap();
+        assert_eq!(forker.program("b"), None);
+
+        let forker = run("a+b,b=sh,b+c", false, false).unwrap();
+        assert_eq!(forker.program("c"), Some("sh"));
+
+        // a zombie keeps its program until it is reaped
+        let (forker, _) = zombies("a+b,b=sh,b-", false).unwrap();
+        assert_eq!(forker.tree().unwrap(), "a\n└── b (sh) <defunct>\n");
+        let (forker, _) = zombies("a+b,b=sh,b-,a*", false).unwrap();
+        assert_eq!(forker.program("b"), None);
+    }
+
+    fn reparented(actions: &str, local_reparent: bool, subreaper: bool) -> Forker {
+        let mut forker = Forker::builder()
+            .action_list(actions.split(',').map(str::to_string).collect())
+            .local_reparent(local_reparent)
+            .subreaper(subreaper)
+            .build();
+        forker.run(&mut io::sink()).unwrap();
+        forker
+    }
+
+    #[test]
+    fn test_three_reparent_strategies() {
+        let actions = "a+b,b^,b+c,c+d,d+e,e+f,d-";
+
+        // fork.py flattens all of d's descendants to the root, the local
+        // mode hands e to d's parent, and Linux hands it to subreaper b,
+        // with f staying under e in both
+        let root = reparented(actions, false, false);
+        assert_eq!(
+            root.tree().unwrap(),
+            "a\n├── b [subreaper]\n│   └── c\n├── e\n└── f\n"
+        );
+
+        let local = reparented(actions, true, false);
+        assert_eq!(
+            local.tree().unwrap(),
+            "a\n└── b [subreaper]\n    └── c\n        └── e\n            └── f\n"
+        );
+
+        let linux = reparented(actions, false, true);
+        assert_eq!(
+            linux.tree().unwrap(),
+            "a\n└── b [subreaper]\n    ├── c\n    └── e\n        └── f\n"
+        );
+        assert_eq!(linux.parent("e"), Some("b"));
+        assert_eq!(linux.parent("f"), Some("e"));
+    }
+
+    #[test]
+    fn test_subreaper_rules() {
+        // without a subreaper above, orphans go to the root, but only the
+        // children of the exiting process
+        let forker = reparented("a+b,b+c,c+d,b-", false, true);
+        assert_eq!(forker.children("a"), ["c"]);
+        assert_eq!(forker.children("c"), ["d"]);
+
+        // the nearest subreaper wins, and the mark is not inherited by forks
+        let forker = reparented("a+b,b^,b+c,c^,c+d,d+e,e+f,e-", false, true);
+        assert!(forker.is_subreaper("c") && !forker.is_subreaper("d"));
+        assert_eq!(forker.parent("f"), Some("c"));
+
+        // nor kept by a name used again
+        let forker = reparented("a+b,b^,b-,a+b", false, true);
+        assert!(!forker.is_subreaper("b"));
+
+        // a subreaper waits for the zombies it takes in, unlike the root
+        let mut forker = Forker::builder()
+            .action_list("a+b,b^,b+c,c+d,d-,c-".split(',').map(str::to_string).collect())
+            .subreaper(true)
+            .zombies(true)
+            .build();
+        forker.run(&mut io::sink()).unwrap();
+        assert_eq!(forker.zombies(), ["d", "c"]);
+        assert_eq!(forker.parent("d"), Some("b"));
+
+    }
+
+    fn killed(actions: &str, kill_descendants: bool, zombies: bool) -> Result<(Forker, String), String> {
+        let mut forker = Forker::builder()
+            .action_list(actions.split(',').map(str::to_string).collect())
+            .kill_descendants(kill_descendants)
+            .zombies(zombies)
+            .solve(true)
+            .build();
+        let mut out = Vec::new();
+        forker.run(&mut out)?;
+        Ok((forker, String::from_utf8(out).unwrap()))
+    }
+
+    #[test]
+    fn test_groups_are_inherited() {
+        let (forker, out) = killed("a+b,b+c,c#,c+d,d+e,b+f", false, false).unwrap();
+
+        assert!(out.contains("Action: c starts group c\n"));
+        assert_eq!(forker.group("a"), Some("a"));
+        assert_eq!(forker.group("b"), Some("a"));
+        assert_eq!(forker.group("e"), Some("c"));
+        assert_eq!(forker.group("f"), Some("a"));
+        assert_eq!(forker.group("x"), None);
+        assert_eq!(
+            forker.tree().unwrap(),
+            "a\n└── b\n    ├── c [group c]\n    │   └── d [group c]\n    │       └── e [group c]\n    └── f\n"
+        );
+    }
+
+    #[test]
+    fn test_kill_orphans_or_takes_descendants() {
+        let actions = "a+b,b+c,c+d,a+e,b!";
+
+        let (forker, out) = killed(actions, false, false).unwrap();
+        assert!(out.contains("Action: b is killed\n"));
+        assert_eq!(forker.processes(), ["a", "c", "d", "e"]);
+
+        let (forker, out) = killed(actions, true, false).unwrap();
+        assert!(out.contains("Action: b is killed, with c, d\n"));
+        assert_eq!(forker.processes(), ["a", "e"]);
+
+        // a killed process is a zombie like any other which exits
+        let (forker, _) = killed(actions, true, true).unwrap();
+        assert_eq!(forker.zombies(), ["b"]);
+    }
+
+    #[test]
+    fn test_kill_group() {
+        let actions = "a+b,b+c,c#,c+d,d+e,e#,e+f,c%";
+
+        let (forker, out) = killed(actions, false, false).unwrap();
+        assert!(out.contains("Action: group c is killed: c, d\n"), "{}", out);
+        assert_eq!(forker.processes(), ["a", "b", "e", "f"]);
+
+        let (forker, out) = killed(actions, true, false).unwrap();
+        assert!(out.contains("Action: group c is killed: c, d, e, f\n"), "{}", out);
+        assert_eq!(forker.processes(), ["a", "b"]);
+
+        // the group outlives c, and its members die wherever they are
+        let (forker, out) = killed("a+b,b+c,c#,c+d,c-,c%", false, false).unwrap();
+        assert!(out.contains("Action: group c is killed: d\n"), "{}", out);
+        assert_eq!(forker.processes(), ["a", "b"]);
+
+        // the root ignores the signal
+        let (forker, out) = killed("a+b,b+c,a%", false, false).unwrap();
+        assert!(out.contains("Action: group a is killed: b, c\n"), "{}", out);
+        assert_eq!(forker.processes(), ["a"]);
+        let (_, out) = killed("a%", true, false).unwrap();
+        assert!(out.contains("Action: group a is killed: no one dies\n"), "{}", out);
+    }
+
+    #[test]
+    fn test_kill_rules() {
+        let cases = [
+            ("a!", "bad action `a!`: the root process cannot be killed"),
+            ("a+b,b!,b!", "bad action `b!`: b is not a live process"),
+            ("a+b,b%", "bad action `b%`: there is no group b"),
+            ("a+b,b-,b#", "bad action `b#`: b is not a live process"),
+            ("a+b,b#,b+c,b-,a+b", "bad action `a+b`: b is still the id of a process group"),
+        ];
+        for (actions, error) in cases {
+            assert_eq!(killed(actions, false, false).err().as_deref(), Some(error), "{}", actions);
+        }
+
+        // once the group is gone, so is its id
+        assert!(killed("a+b,b#,b+c,b-,c!,a+b", false, false).is_ok());
+    }
+}
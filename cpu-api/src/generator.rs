@@ -1,166 +1,507 @@
-use std::fs::File;
-use std::io::Write;
+//! C programs built from fork actions, as `generator.py` writes them.
 
-use clap::Parser;
+use std::fmt;
+use std::io::{self, Write};
 
-use crate::seed;
+use ostep_rng::Random;
 
-// Boilerplate code for `.c` files used by both readable/runnable code versions
-struct Boilerplate {
-    fd: File,
+// limits on what an action list may ask for
+const MAX_SLEEP: u32 = 60;
+const MAX_DEPTH: usize = 32;
+
+/// What a process of a generated program does.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Action {
+    /// Fork the named child, which does the given actions and then exits.
+    Fork(String, Vec<Action>),
+    /// Wait for any child to exit.
+    Wait,
+    /// Sleep for some seconds.
+    Sleep(u32),
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Action::Fork(child, code) => {
+                let code = code.iter().map(|a| a.to_string()).collect::<Vec<String>>();
+                write!(f, "fork {}({})", child, code.join(","))
+            },
+            Action::Wait => write!(f, "wait"),
+            Action::Sleep(secs) => write!(f, "sleep {}", secs),
+        }
+    }
+}
+
+/// Parse the actions of the root process `a`, like
+/// `fork b(sleep 1,fork c),wait,sleep 2,wait`: the brackets after a fork
+/// hold what the child does before it exits.
+///
+/// # Errors
+///
+/// Fails on an unknown action, on a name which is not a single letter from
+/// `b` to `z` or is used twice, and on a `wait` without a child to wait for.
+pub fn parse_actions(actions: &str) -> Result<Vec<Action>, String> {
+    let mut names = vec!["a".to_string()];
+    let code = parse_code(actions.trim(), 0, &mut names)?;
+    check_waits(&code)?;
+    Ok(code)
+}
+
+fn parse_code(actions: &str, depth: usize, names: &mut Vec<String>) -> Result<Vec<Action>, String> {
+    if depth > MAX_DEPTH {
+        return Err(format!("forks nested deeper than {}", MAX_DEPTH));
+    }
+
+    if actions.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut code = Vec::new();
+    for action in split_actions(actions)? {
+        code.push(parse_action(action.trim(), depth, names)?);
+    }
+    Ok(code)
+}
+
+// split on the commas which are not inside the code of a child
+fn split_actions(actions: &str) -> Result<Vec<&str>, String> {
+    let mut parts = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+
+    for (i, ch) in actions.char_indices() {
+        match ch {
+            '(' => depth += 1,
+            ')' if depth == 0 => return Err(format!("unbalanced `)` in `{}`", actions)),
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(&actions[start..i]);
+                start = i + 1;
+            },
+            _ => {},
+        }
+    }
+
+    if depth != 0 {
+        return Err(format!("unbalanced `(` in `{}`", actions));
+    }
+    parts.push(&actions[start..]);
+    Ok(parts)
+}
+
+fn parse_action(action: &str, depth: usize, names: &mut Vec<String>) -> Result<Action, String> {
+    let bad_action = || {
+        format!(
+            "bad action `{}`: must be `fork X`, `fork X(...)`, `wait` or `sleep N`",
+            action
+        )
+    };
+
+    if action == "wait" {
+        return Ok(Action::Wait);
+    }
+
+    if let Some(secs) = action.strip_prefix("sleep ") {
+        let secs = secs.trim().parse::<u32>().map_err(|_| bad_action())?;
+        if secs > MAX_SLEEP {
+            return Err(format!("bad action `{}`: sleep at most {} seconds", action, MAX_SLEEP));
+        }
+        return Ok(Action::Sleep(secs));
+    }
+
+    let fork = action.strip_prefix("fork ").ok_or_else(bad_action)?.trim();
+    let (child, code) = match fork.split_once('(') {
+        Some((child, code)) => (child.trim(), code.strip_suffix(')').ok_or_else(bad_action)?),
+        None => (fork, ""),
+    };
+
+    // the timeline indents each process by its letter
+    if child.len() != 1 || !matches!(child.as_bytes()[0], b'b'..=b'z') {
+        return Err(format!("bad process name `{}`: must be one letter from b to z", child));
+    }
+    if names.iter().any(|name| name == child) {
+        return Err(format!("process `{}` is forked twice", child));
+    }
+    names.push(child.to_string());
+
+    Ok(Action::Fork(child.to_string(), parse_code(code.trim(), depth + 1, names)?))
+}
+
+// a process can only wait while it has children it has not waited for
+fn check_waits(code: &[Action]) -> Result<(), String> {
+    let mut children = 0;
+
+    for action in code {
+        match action {
+            Action::Fork(_, child_code) => {
+                check_waits(child_code)?;
+                children += 1;
+            },
+            Action::Wait if children == 0 => {
+                return Err("`wait` without a child to wait for".to_string());
+            },
+            Action::Wait => children -= 1,
+            Action::Sleep(_) => {},
+        }
+    }
+    Ok(())
+}
+
+// a step of a process while the random actions are drawn; forks refer to
+// the child by its index, the order in which the processes were created
+enum Step {
+    Fork(usize),
+    Do(Action),
+}
+
+/// Random actions with `num_forks` forks (at most 25), drawn from `rng`:
+/// each fork is made by a random existing process, which may sleep before
+/// it and wait right after it.
+pub fn random_actions(rng: &mut Random, num_forks: usize) -> Vec<Action> {
+    let mut procs: Vec<Vec<Step>> = vec![Vec::new()];
+
+    for _ in 0..num_forks.min(25) {
+        let parent = rng.randint(0, procs.len() as i64 - 1) as usize;
+
+        if rng.random() < 0.5 {
+            let secs = rng.randint(1, 3) as u32;
+            procs[parent].push(Step::Do(Action::Sleep(secs)));
+        }
+
+        procs.push(Vec::new());
+        let child = procs.len() - 1;
+        procs[parent].push(Step::Fork(child));
+
+        if rng.random() < 0.5 {
+            procs[parent].push(Step::Do(Action::Wait));
+        }
+    }
+
+    nest(&procs, 0)
+}
+
+// the actions of process `p`, with the code of its children inside
+fn nest(procs: &[Vec<Step>], p: usize) -> Vec<Action> {
+    procs[p]
+        .iter()
+        .map(|step| match step {
+            Step::Fork(c) => Action::Fork(((b'a' + *c as u8) as char).to_string(), nest(procs, *c)),
+            Step::Do(action) => action.clone(),
+        })
+        .collect()
 }
 
-impl Boilerplate {
-    fn new(fd: File) -> Self {
+/// Boilerplate code for `.c` files used by both readable/runnable code
+/// versions, written to any sink.
+pub struct Boilerplate<W: Write> {
+    fd: W,
+}
+
+impl<W: Write> Boilerplate<W> {
+    /// Boilerplate written to `fd`.
+    pub fn new(fd: W) -> Self {
         Self { fd }
     }
 
-    fn init(&mut self) {
-        self.fd.write(b"
-#include <assert.h>\n
-#include <stdio.h>\n
-#include <stdlib.h>\n
-#include <string.h>\n
-#include <sys/time.h>\n
-#include <sys/wait.h>\n
-#include <unistd.h>\n\n
-
-void wait_or_die() {\n
-    int rc = wait(NULL);\n
-    assert(rc > 0);\n
-}\n\n
-
-int for_or_die() {\n
-    int rc = fork();\n
-    assert(rc >= 0);\n
-    return rc;\n
-}\n\n
-").expect("Failed to write to file");
-    }
-
-    fn init_runnable(&mut self) {
-        self.init();
-        self.fd.write(b"
-#define TimeGetSeconds() ({ struct timeval t; int rc = gettimeofday(&t, NULL); assert(rc == 0); (double) t.tv_sec + (double) t.tv_usec/1e6; }) \n\n
-
-double t_start;\n\n
-
-struct pid_map {\n
-    int pid;\n
-    char name[10];\n
-    struct pid_map *next; \n
-};\n\n
-
-struct pid_map *head = NULL;\n\n
-
-void Space(char c) {\n
-    int i;\n
-    for (i = 0; i < 5 * (c - \'a\'); i++) {\n
-        printf(\" \");\n
-    }\n
-}\n\n
-
-char *Lookup(int pid) {\n
-    struct pid_map *curr = head;\n
-    while (curr) {\n
-        if (curr->pid == pid) {\n
-            return curr->name;\n
-        }\n
-        curr = curr->next;\n
-    }\n
-    return NULL;\n
-}\n\n
-
-void Record(int pid, char *m) {\n
-    struct pid_map *n = malloc(sizeof(struct pid_map));\n
-    assert(n);\n
-    n->pid = pid;\n
-    strcpy(n->name, m);\n
-    n->next = head;\n
-    head = n;\n
-}\n\n
-
-void Wait(char *m) {\n
-    int rc = wait(NULL);\n
-    assert(rc > 0);\n
-    double t = TimeGetSeconds() - t_start;\n
-    printf(\"&3d\", (int)t);\n
-    Space(m[0]);\n
-    char *n = Lookup(rc);\n
-    assert(n != NULL);\n
-    printf(\"%s<-%s\\n\", m, n);\n
-}\n\n
-
-void Sleep(int s, char *m) {\n
-    sleep(s);\n
-    double t = TimeGetSeconds() - t_start;\n
-    printf(\"%3d %s\", (int)t, m);\n
-}\n\n
-
-void Fork(char *p, char *c) {\n
-    double t = TimeGetSeconds() - t_start;\n
-    printf(\"%3d\", (int)t);\n
-    Space(p[0]);\n
-    printf(\"%s->%s\\n\", p, c);\n
-}\n\n
-
-void __Begin(char *m) {\n
-    double t = TimeGetSeconds() - t_start;\n
-    printf(\"%3d\", (int)t);\n
-    Space(m[0]);\n
-    printf(\"%s+\\n\", m);\n
-}\n\n
-
-void __End(char *m) {\n
-    double t = TimeGetSeconds() - t_start;\n
-    printf(\"%3d\", (int)t);\n
-    Space(m[0]);\n
-    printf(\"%s-\\n\", m);\n
-}\n\n
-
-#define Begin(m) { __Begin(m); }\n
-#define End(m) { __End(m); exit(0); }\n
-        ").expect("Fail to write to file");
-    }
-
-    fn finish(&mut self) {
-        self.fd.write(
-b"    return 0;\n
-}\n
-").expect("Failed to write to file");
-    }
-
-    fn main(&mut self) {
-        self.main();
-        self.fd.write(
-b"      int rc;\n
-        t_start = TimeGetSeconds();\n
-"
-        ).expect("Failed to write to file");
-    }
-}
-
-struct CodeGeneratorReadable {
-    fd: File,
-    boiler: Boilerplate,
+    /// Give back the sink.
+    pub fn into_inner(self) -> W {
+        self.fd
+    }
+
+    /// The includes and the `fork`/`wait` wrappers every program needs.
+    pub fn init(&mut self) -> io::Result<()> {
+        self.fd.write_all(br#"#include <assert.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <sys/time.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+void wait_or_die() {
+    int rc = wait(NULL);
+    assert(rc > 0);
+}
+
+int fork_or_die() {
+    // the child must not print what is still buffered in the parent
+    fflush(stdout);
+    int rc = fork();
+    assert(rc >= 0);
+    return rc;
+}
+
+"#)
+    }
+
+    /// [`Boilerplate::init`] plus the helpers which print a timeline when
+    /// the program runs.
+    pub fn init_runnable(&mut self) -> io::Result<()> {
+        self.init()?;
+        self.fd.write_all(br#"#define TimeGetSeconds() ({ struct timeval t; int rc = gettimeofday(&t, NULL); assert(rc == 0); (double) t.tv_sec + (double) t.tv_usec/1e6; })
+
+double t_start;
+
+struct pid_map {
+    int pid;
+    char name[10];
+    struct pid_map *next;
+};
+
+struct pid_map *head = NULL;
+
+void Space(char c) {
+    int i;
+    for (i = 0; i < 5 * (c - 'a'); i++) {
+        printf(" ");
+    }
+}
+
+char *Lookup(int pid) {
+    struct pid_map *curr = head;
+    while (curr) {
+        if (curr->pid == pid) {
+            return curr->name;
+        }
+        curr = curr->next;
+    }
+    return NULL;
+}
+
+void Record(int pid, char *m) {
+    struct pid_map *n = malloc(sizeof(struct pid_map));
+    assert(n);
+    n->pid = pid;
+    strcpy(n->name, m);
+    n->next = head;
+    head = n;
+}
+
+void Wait(char *m) {
+    int rc = wait(NULL);
+    assert(rc > 0);
+    double t = TimeGetSeconds() - t_start;
+    printf("%3d ", (int)t);
+    Space(m[0]);
+    char *n = Lookup(rc);
+    assert(n != NULL);
+    printf("%s<-%s\n", m, n);
+}
+
+void Sleep(int s) {
+    sleep(s);
+}
+
+void Fork(char *p, char *c) {
+    double t = TimeGetSeconds() - t_start;
+    printf("%3d ", (int)t);
+    Space(p[0]);
+    printf("%s->%s\n", p, c);
+}
+
+void __Begin(char *m) {
+    double t = TimeGetSeconds() - t_start;
+    printf("%3d ", (int)t);
+    Space(m[0]);
+    printf("%s+\n", m);
+}
+
+void __End(char *m) {
+    double t = TimeGetSeconds() - t_start;
+    printf("%3d ", (int)t);
+    Space(m[0]);
+    printf("%s-\n", m);
+}
+
+#define Begin(m) { __Begin(m); }
+#define End(m) { __End(m); exit(0); }
+
+"#)
+    }
+
+    /// The end of `main`.
+    pub fn finish(&mut self) -> io::Result<()> {
+        self.fd.write_all(b"    return 0;\n}\n")
+    }
+
+    /// The start of `main`.
+    pub fn main(&mut self) -> io::Result<()> {
+        self.fd.write_all(b"int main(int argc, char *argv[]) {\n")
+    }
+
+    /// [`Boilerplate::main`] plus starting the clock of the timeline.
+    pub fn main_runnable(&mut self) -> io::Result<()> {
+        self.main()?;
+        // unbuffered, so the lines of all processes come out as they happen
+        self.fd.write_all(b"    int rc;\n    setbuf(stdout, NULL);\n    t_start = TimeGetSeconds();\n")
+    }
+
+    // one line of code, indented by `tab_level` tabs of four spaces
+    fn line(&mut self, tab_level: u32, line: &str) -> io::Result<()> {
+        writeln!(self.fd, "{:indent$}{}", "", line, indent = 4 * tab_level as usize)
+    }
+}
+
+/// Writes the readable version of a program: plain C without the timeline
+/// helpers.
+pub struct CodeGeneratorReadable<W: Write> {
+    boiler: Boilerplate<W>,
+    // indentation of the code being written, in tabs
     tab_level: u32,
     out_file: String,
-    actions: Vec<String>,
+    actions: Vec<Action>,
 }
 
-impl CodeGeneratorReadable {
-    fn new(self, fd: File, out_file: String, actions: Vec<String>) -> Self {
+impl<W: Write> CodeGeneratorReadable<W> {
+    /// A generator writing to `fd` the program named `out_file` (`.c` is
+    /// appended) for `actions`.
+    pub fn new(fd: W, out_file: String, actions: Vec<Action>) -> Self {
         let out_file = out_file + ".c";
-        let actions = actions;
         let tab_level = 1;
         let boiler = Boilerplate::new(fd);
 
         Self {
-            fd,
             boiler,
             tab_level,
             out_file,
             actions,
         }
     }
-}
\ No newline at end of file
+
+    /// Name of the program, `.c` included.
+    pub fn out_file(&self) -> &str {
+        &self.out_file
+    }
+
+    /// The actions the program performs.
+    pub fn actions(&self) -> &[Action] {
+        &self.actions
+    }
+
+    /// The boilerplate writer, to write the program around the actions.
+    pub fn boilerplate(&mut self) -> &mut Boilerplate<W> {
+        &mut self.boiler
+    }
+
+    /// Give back the sink.
+    pub fn into_inner(self) -> W {
+        self.boiler.into_inner()
+    }
+
+    /// Write the whole program.
+    pub fn generate(&mut self) -> io::Result<()> {
+        self.boiler.init()?;
+        self.boiler.main()?;
+
+        let actions = self.actions.clone();
+        self.write_actions(&actions)?;
+
+        self.boiler.finish()
+    }
+
+    fn write_actions(&mut self, actions: &[Action]) -> io::Result<()> {
+        for action in actions {
+            match action {
+                Action::Fork(child, code) => {
+                    let fork = format!("if (fork_or_die() == 0) {{ // {}", child);
+                    self.boiler.line(self.tab_level, &fork)?;
+                    self.tab_level += 1;
+                    self.write_actions(code)?;
+                    self.boiler.line(self.tab_level, "exit(0);")?;
+                    self.tab_level -= 1;
+                    self.boiler.line(self.tab_level, "}")?;
+                },
+                Action::Wait => {
+                    self.boiler.line(self.tab_level, "wait_or_die();")?;
+                },
+                Action::Sleep(secs) => {
+                    self.boiler.line(self.tab_level, &format!("sleep({});", secs))?;
+                },
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Writes the runnable version of a program, which prints a timeline of
+/// its processes starting (`b+`), forking (`a->b`), collecting a child
+/// (`a<-b`) and ending (`b-`).
+pub struct CodeGeneratorRunnable<W: Write> {
+    boiler: Boilerplate<W>,
+    // indentation of the code being written, in tabs
+    tab_level: u32,
+    out_file: String,
+    actions: Vec<Action>,
+}
+
+impl<W: Write> CodeGeneratorRunnable<W> {
+    /// A generator writing to `fd` the program named `out_file` (`.c` is
+    /// appended) for `actions`.
+    pub fn new(fd: W, out_file: String, actions: Vec<Action>) -> Self {
+        Self {
+            boiler: Boilerplate::new(fd),
+            tab_level: 1,
+            out_file: out_file + ".c",
+            actions,
+        }
+    }
+
+    /// Name of the program, `.c` included.
+    pub fn out_file(&self) -> &str {
+        &self.out_file
+    }
+
+    /// The actions the program performs.
+    pub fn actions(&self) -> &[Action] {
+        &self.actions
+    }
+
+    /// The boilerplate writer, to write the program around the actions.
+    pub fn boilerplate(&mut self) -> &mut Boilerplate<W> {
+        &mut self.boiler
+    }
+
+    /// Give back the sink.
+    pub fn into_inner(self) -> W {
+        self.boiler.into_inner()
+    }
+
+    /// Write the whole program.
+    pub fn generate(&mut self) -> io::Result<()> {
+        self.boiler.init_runnable()?;
+        self.boiler.main_runnable()?;
+        self.boiler.line(self.tab_level, "Begin(\"a\");")?;
+
+        let actions = self.actions.clone();
+        self.write_actions("a", &actions)?;
+
+        self.boiler.line(self.tab_level, "End(\"a\");")?;
+        self.boiler.finish()
+    }
+
+    fn write_actions(&mut self, name: &str, actions: &[Action]) -> io::Result<()> {
+        for action in actions {
+            match action {
+                Action::Fork(child, code) => {
+                    self.boiler.line(self.tab_level, "if ((rc = fork_or_die()) == 0) {")?;
+                    self.tab_level += 1;
+                    self.boiler.line(self.tab_level, &format!("Begin(\"{}\");", child))?;
+                    self.write_actions(child, code)?;
+                    self.boiler.line(self.tab_level, &format!("End(\"{}\");", child))?;
+                    self.tab_level -= 1;
+                    self.boiler.line(self.tab_level, "}")?;
+                    self.boiler.line(self.tab_level, &format!("Record(rc, \"{}\");", child))?;
+                    self.boiler.line(self.tab_level, &format!("Fork(\"{}\", \"{}\");", name, child))?;
+                },
+                Action::Wait => {
+                    self.boiler.line(self.tab_level, &format!("Wait(\"{}\");", name))?;
+                },
+                Action::Sleep(secs) => {
+                    self.boiler.line(self.tab_level, &format!("Sleep({});", secs))?;
+                },
+            }
+        }
+        Ok(())
+    }
+}
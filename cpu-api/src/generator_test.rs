@@ -0,0 +1,122 @@
+#[cfg(test)]
+mod tests {
+    use ostep_rng::Random;
+
+    use crate::generator::{
+        parse_actions, random_actions, Action, Boilerplate, CodeGeneratorReadable,
+        CodeGeneratorRunnable,
+    };
+
+    #[test]
+    fn test_boilerplate_writes_to_any_sink() {
+        let mut boiler = Boilerplate::new(Vec::new());
+        boiler.init().unwrap();
+        boiler.main().unwrap();
+        boiler.finish().unwrap();
+
+        let code = String::from_utf8(boiler.into_inner()).unwrap();
+        assert!(code.contains("#include <sys/wait.h>"));
+        assert!(code.contains("int main(int argc, char *argv[]) {"));
+        assert!(code.trim_end().ends_with('}'));
+        assert!(!code.contains("void Record("));
+    }
+
+    #[test]
+    fn test_parse_actions() {
+        let actions = parse_actions("fork b(sleep 1, fork c), wait,sleep 2").unwrap();
+
+        assert_eq!(
+            actions,
+            vec![
+                Action::Fork(
+                    "b".to_string(),
+                    vec![Action::Sleep(1), Action::Fork("c".to_string(), vec![])],
+                ),
+                Action::Wait,
+                Action::Sleep(2),
+            ]
+        );
+        assert_eq!(
+            actions.iter().map(|a| a.to_string()).collect::<Vec<String>>().join(","),
+            "fork b(sleep 1,fork c()),wait,sleep 2"
+        );
+        assert_eq!(parse_actions(""), Ok(vec![]));
+    }
+
+    #[test]
+    fn test_parse_actions_rejects_bad_input() {
+        for actions in [
+            "fork a", "fork B", "fork bc", "fork é", "fork b,fork b", "fork b(fork b)", "wait",
+            "fork b(wait)", "fork b,wait,wait", "sleep", "sleep x", "sleep 61", "exit",
+            "fork b(", "fork b)", "fork b(sleep 1)x", ",",
+        ] {
+            assert!(parse_actions(actions).is_err(), "`{}` parsed", actions);
+        }
+    }
+
+    #[test]
+    fn test_random_actions_are_reproducible() {
+        let actions = random_actions(&mut Random::new(3), 6);
+        assert_eq!(random_actions(&mut Random::new(3), 6), actions);
+        assert_ne!(random_actions(&mut Random::new(4), 6), actions);
+
+        // what is drawn parses back to itself
+        let text = actions.iter().map(|a| a.to_string()).collect::<Vec<String>>().join(",");
+        assert_eq!(parse_actions(&text), Ok(actions));
+        assert_eq!(text.matches("fork").count(), 6);
+    }
+
+    #[test]
+    fn test_readable_generator() {
+        let actions = parse_actions("fork b(sleep 1),wait").unwrap();
+        let mut generator = CodeGeneratorReadable::new(Vec::new(), "prog".to_string(), actions.clone());
+        generator.generate().unwrap();
+
+        assert_eq!(generator.out_file(), "prog.c");
+        assert_eq!(generator.actions(), actions);
+
+        let code = String::from_utf8(generator.into_inner()).unwrap();
+        assert!(code.ends_with(
+            "int main(int argc, char *argv[]) {
+    if (fork_or_die() == 0) { // b
+        sleep(1);
+        exit(0);
+    }
+    wait_or_die();
+    return 0;
+}
+"
+        ));
+    }
+
+    #[test]
+    fn test_runnable_generator() {
+        let actions = parse_actions("fork b(fork c,wait),wait").unwrap();
+        let mut generator = CodeGeneratorRunnable::new(Vec::new(), "prog".to_string(), actions);
+        generator.generate().unwrap();
+
+        let code = String::from_utf8(generator.into_inner()).unwrap();
+        assert!(code.contains("void Record("));
+        assert!(code.ends_with(
+            "    Begin(\"a\");
+    if ((rc = fork_or_die()) == 0) {
+        Begin(\"b\");
+        if ((rc = fork_or_die()) == 0) {
+            Begin(\"c\");
+            End(\"c\");
+        }
+        Record(rc, \"c\");
+        Fork(\"b\", \"c\");
+        Wait(\"b\");
+        End(\"b\");
+    }
+    Record(rc, \"b\");
+    Fork(\"a\", \"b\");
+    Wait(\"a\");
+    End(\"a\");
+    return 0;
+}
+"
+        ));
+    }
+}
@@ -0,0 +1,29 @@
+//! A port of `fork.py` and `generator.py` from the OSTEP homework on the
+//! process API.
+//!
+//! [`Forker`] grows and shrinks a process tree with forks and exits and
+//! prints it; [`quiz`] finds the actions behind its trees; [`procfs`] reads
+//! real ones and `trace` records real actions; [`generator`] writes C
+//! programs doing the same.
+
+#![warn(missing_docs)]
+
+pub mod cli;
+mod diagram;
+pub mod fork;
+pub mod generator;
+pub mod procfs;
+pub mod quiz;
+#[cfg(target_os = "linux")]
+pub mod trace;
+
+mod cli_test;
+mod diagram_test;
+mod fork_test;
+mod generator_test;
+mod procfs_test;
+mod quiz_test;
+#[cfg(target_os = "linux")]
+mod trace_test;
+
+pub use fork::{Forker, ForkerBuilder};
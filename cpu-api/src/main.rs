@@ -1,7 +1,50 @@
-mod fork;
-mod generator;
-mod seed;
+use clap::{Parser, Subcommand};
+
+use cpu_api::cli::{self, ForkOptions, GeneratorOptions};
+
+// like fork.py, the options of fork run at the top level, e.g.
+//   cpu-api -s 10 -a 5 -c
+//   cpu-api generator -A "fork b,wait"
+#[derive(Parser, Debug)]
+#[command(about = "Simulate process trees and write programs which fork")]
+#[command(args_conflicts_with_subcommands = true)]
+struct Args {
+    /// the random seed
+    #[arg(short = 's', long, global = true, default_value_t = 0)]
+    seed: u64,
+
+    /// compute answers for me
+    #[arg(short = 'c', long, global = true)]
+    solve: bool,
+
+    #[command(flatten)]
+    fork: ForkOptions,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// grow a process tree with forks and exits (fork.py); the same as
+    /// giving its options without the subcommand
+    Fork(ForkOptions),
+
+    /// write C programs which fork and wait (generator.py)
+    Generator(GeneratorOptions),
+}
 
 fn main() {
-    println!("Hello, world!");
+    let args = Args::parse();
+
+    let result = match &args.command {
+        None => cli::fork(&args.fork, args.seed, args.solve, &mut std::io::stdout()),
+        Some(Command::Fork(options)) => cli::fork(options, args.seed, args.solve, &mut std::io::stdout()),
+        Some(Command::Generator(options)) => cli::generator(options, args.seed, args.solve, &mut std::io::stdout()),
+    };
+
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
 }
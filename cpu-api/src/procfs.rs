@@ -0,0 +1,118 @@
+//! Live process trees read from Linux's `/proc`, to hold the model up
+//! against the real thing.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::fork::{Forker, ForkerBuilder};
+
+/// What `/proc/<pid>/stat` tells about a process.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Stat {
+    /// Process id.
+    pub pid: u32,
+    /// Command name, at most 15 bytes of the program name.
+    pub comm: String,
+    /// State: `R` running, `S` sleeping, `Z` zombie and so on.
+    pub state: char,
+    /// Process id of the parent.
+    pub ppid: u32,
+    /// Id of the process group.
+    pub pgrp: u32,
+}
+
+impl std::str::FromStr for Stat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bad_stat = || format!("bad stat `{}`", s.trim_end());
+
+        // the command name is in brackets and may hold anything, brackets
+        // and spaces included, so it runs to the last `)`
+        let (Some(open), Some(close)) = (s.find('('), s.rfind(')')) else {
+            return Err(bad_stat());
+        };
+        if close < open {
+            return Err(bad_stat());
+        }
+
+        let fields: Vec<&str> = s[close + 1..].split_whitespace().collect();
+        let number = |field: Option<&&str>| field.and_then(|f| f.parse().ok()).ok_or_else(bad_stat);
+        let state = fields.first().and_then(|f| f.chars().next()).ok_or_else(bad_stat)?;
+
+        Ok(Stat {
+            pid: s[..open].trim().parse().map_err(|_| bad_stat())?,
+            comm: s[open + 1..close].to_string(),
+            state,
+            ppid: number(fields.get(1))?,
+            pgrp: number(fields.get(2))?,
+        })
+    }
+}
+
+fn read_stat(proc: &Path, pid: u32) -> Result<Stat, String> {
+    let stat = fs::read_to_string(proc.join(pid.to_string()).join("stat")).map_err(|e| e.to_string())?;
+    stat.parse()
+}
+
+// the children of every thread of `pid`, lowest pid first
+fn read_children(proc: &Path, pid: u32) -> io::Result<Vec<u32>> {
+    let mut children = Vec::new();
+
+    for task in fs::read_dir(proc.join(pid.to_string()).join("task"))? {
+        let list = fs::read_to_string(task?.path().join("children"))?;
+        children.extend(list.split_whitespace().filter_map(|c| c.parse::<u32>().ok()));
+    }
+    children.sort_unstable();
+    children.dedup();
+    Ok(children)
+}
+
+/// The live tree of processes under `pid`, read from `/proc`: every
+/// process is named by its pid and runs its command name, as if it had
+/// exec'd it, in its real process group; zombies are zombies. `options`
+/// set how the tree is drawn.
+///
+/// # Errors
+///
+/// Fails if `pid` cannot be read, or its children cannot be listed, which
+/// needs a kernel with `CONFIG_PROC_CHILDREN`. Processes which exit while
+/// the tree is read are left out.
+pub fn read_tree(options: &ForkerBuilder, pid: u32) -> Result<Forker, String> {
+    read_tree_in(Path::new("/proc"), options, pid)
+}
+
+// `read_tree` with `proc` as `/proc`
+pub(crate) fn read_tree_in(proc: &Path, options: &ForkerBuilder, pid: u32) -> Result<Forker, String> {
+    let stat = read_stat(proc, pid).map_err(|e| format!("cannot read process {}: {}", pid, e))?;
+    let children = read_children(proc, pid).map_err(|e| format!("cannot list the children of process {}: {}", pid, e))?;
+
+    let root = pid.to_string();
+    let mut forker = options.clone().root(&root).action_list(Vec::new()).build();
+    forker.do_exec(&root, &stat.comm);
+    forker.set_group(&root, &stat.pgrp.to_string());
+
+    let mut todo = vec![(pid, children)];
+    while let Some((p, children)) = todo.pop() {
+        for c in children {
+            // gone since its parent was read
+            let Ok(stat) = read_stat(proc, c) else {
+                continue;
+            };
+            let name = c.to_string();
+            if forker.processes().contains(&name) || forker.is_zombie(&name) {
+                continue;
+            }
+
+            forker.do_fork(&p.to_string(), &name);
+            forker.do_exec(&name, &stat.comm);
+            forker.set_group(&name, &stat.pgrp.to_string());
+            if stat.state == 'Z' {
+                forker.set_defunct(&name);
+            }
+            todo.push((c, read_children(proc, c).unwrap_or_default()));
+        }
+    }
+    Ok(forker)
+}
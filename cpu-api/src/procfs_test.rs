@@ -0,0 +1,126 @@
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+    use std::process::{Child, Command};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use crate::fork::Forker;
+    use crate::procfs::{read_tree, read_tree_in, Stat};
+
+    // a `/proc` of its own for each test, holding `(pid, stat, children)`
+    fn fake_proc(test: &str, processes: &[(u32, &str, &str)]) -> PathBuf {
+        let proc = std::env::temp_dir().join(format!("ostep-proc-{}-{}", test, std::process::id()));
+        let _ = fs::remove_dir_all(&proc);
+
+        for (pid, stat, children) in processes {
+            let task = proc.join(pid.to_string()).join("task").join(pid.to_string());
+            fs::create_dir_all(&task).unwrap();
+            fs::write(proc.join(pid.to_string()).join("stat"), format!("{}\n", stat)).unwrap();
+            fs::write(task.join("children"), children).unwrap();
+        }
+        proc
+    }
+
+    #[test]
+    fn test_stat_parse() {
+        let stat: Stat = "17791 (bash) S 7323 17791 17791 0 -1 4194304 320".parse().unwrap();
+        assert_eq!(
+            stat,
+            Stat {
+                pid: 17791,
+                comm: "bash".to_string(),
+                state: 'S',
+                ppid: 7323,
+                pgrp: 17791,
+            }
+        );
+
+        // the command name may hold brackets and spaces
+        let stat: Stat = "42 (a) (b c) Z 1 40 40".parse().unwrap();
+        assert_eq!(stat.comm, "a) (b c");
+        assert_eq!((stat.state, stat.ppid, stat.pgrp), ('Z', 1, 40));
+
+        for bad in ["", "42", "42 (sh", "42 sh) S 1 42", "x (sh) S 1 42", "42 (sh) S", "42 (sh) S 1 x", ") 42 ( S 1 42"] {
+            assert!(bad.parse::<Stat>().is_err(), "`{}` parsed", bad);
+        }
+    }
+
+    #[test]
+    fn test_read_tree_from_stat_and_children() {
+        let proc = fake_proc(
+            "tree",
+            &[
+                (10, "10 (init) S 0 10 10", "11 12 "),
+                (11, "11 (sh) S 10 11 11", "13"),
+                (12, "12 (my daemon) S 10 10 10", ""),
+                (13, "13 (sleep) Z 11 11 11", ""),
+            ],
+        );
+        let forker = read_tree_in(&proc, &Forker::builder(), 10).unwrap();
+
+        assert_eq!(forker.processes(), ["10", "11", "12"]);
+        assert_eq!(forker.zombies(), ["13"]);
+        assert_eq!(forker.parent("13"), Some("11"));
+        assert_eq!(forker.program("12"), Some("my daemon"));
+        assert_eq!(forker.group("11"), Some("11"));
+        assert_eq!(
+            forker.tree().unwrap(),
+            "10 (init)\n├── 11 (sh) [group 11]\n│   └── 13 (sleep) [group 11] <defunct>\n└── 12 (my daemon)\n"
+        );
+
+        let _ = fs::remove_dir_all(proc);
+    }
+
+    #[test]
+    fn test_read_tree_skips_what_is_gone() {
+        // 12 exited between the two reads, and 11 lists no children
+        let proc = fake_proc("gone", &[(10, "10 (init) S 0 10 10", "11 12"), (11, "11 (sh) S 10 10 10", "")]);
+        fs::remove_file(proc.join("11").join("task").join("11").join("children")).unwrap();
+
+        let forker = read_tree_in(&proc, &Forker::builder().print_style("basic"), 10).unwrap();
+        assert_eq!(forker.tree().unwrap(), "10 (init)\n    11 (sh)\n");
+
+        assert!(read_tree_in(&proc, &Forker::builder(), 12)
+            .unwrap_err()
+            .starts_with("cannot read process 12: "));
+        assert!(read_tree_in(&proc, &Forker::builder(), 11)
+            .unwrap_err()
+            .starts_with("cannot list the children of process 11: "));
+
+        let _ = fs::remove_dir_all(proc);
+    }
+
+    #[cfg(target_os = "linux")]
+    fn state(child: &Child) -> Option<char> {
+        let stat = fs::read_to_string(format!("/proc/{}/stat", child.id())).ok()?;
+        stat.parse::<Stat>().ok().map(|stat| stat.state)
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_read_live_children() {
+        let mut sleeper = Command::new("sleep").arg("30").spawn().unwrap();
+        let mut done = Command::new("true").spawn().unwrap();
+
+        // until `true` is waited for, it stays a zombie
+        let start = Instant::now();
+        while state(&done) != Some('Z') && start.elapsed() < Duration::from_secs(10) {
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        let forker = read_tree(&Forker::builder(), std::process::id()).unwrap();
+        let (me, sleeper_pid, done_pid) = (std::process::id().to_string(), sleeper.id().to_string(), done.id().to_string());
+
+        assert_eq!(forker.parent(&sleeper_pid), Some(me.as_str()));
+        assert_eq!(forker.program(&sleeper_pid), Some("sleep"));
+        assert!(forker.processes().contains(&sleeper_pid));
+        assert!(forker.is_zombie(&done_pid));
+        assert_eq!(forker.group(&sleeper_pid), forker.group(&me));
+
+        sleeper.kill().unwrap();
+        sleeper.wait().unwrap();
+        done.wait().unwrap();
+    }
+}
@@ -0,0 +1,339 @@
+//! The `-t` quiz of `fork.py` the other way round: from the trees, find the
+//! actions which grew them.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+
+use crate::fork::{Forker, ForkerBuilder, ACTION_MARKS};
+
+// how many trees a search may visit before giving up
+const MAX_STATES: usize = 200_000;
+
+/// A process tree: the parent of every live process but the configured root
+/// (`a` unless set with [`ForkerBuilder::root`]).
+/// Unlike the printed trees, the order of the children does not matter.
+///
+/// ```
+/// use cpu_api::quiz::Tree;
+///
+/// let tree: Tree = "a(b(d),c)".parse().unwrap();
+/// assert_eq!(tree.parent("d"), Some("b"));
+/// assert_eq!(tree.to_string(), "a(b(d),c)");
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Tree {
+    root: String,
+    parents: BTreeMap<String, String>,
+}
+
+impl Tree {
+    /// The tree `forker` has now.
+    pub fn of(forker: &Forker) -> Self {
+        let mut parents = BTreeMap::new();
+        let mut root = String::new();
+
+        for p in forker.processes() {
+            match forker.parent(p) {
+                Some(parent) => {
+                    parents.insert(p.clone(), parent.to_string());
+                },
+                None => root = p.clone(),
+            }
+        }
+        Tree { root, parents }
+    }
+
+    /// Parent of process `p`; the root and processes not in the tree have
+    /// none.
+    pub fn parent(&self, p: &str) -> Option<&str> {
+        self.parents.get(p).map(String::as_str)
+    }
+
+    fn names(&self) -> impl Iterator<Item = &String> {
+        std::iter::once(&self.root).chain(self.parents.keys())
+    }
+
+    fn children(&self, p: &str) -> Vec<&String> {
+        self.parents
+            .iter()
+            .filter(|(_, parent)| *parent == p)
+            .map(|(child, _)| child)
+            .collect()
+    }
+
+    // forks which grow this tree from just its root, parents first
+    fn forks(&self) -> Vec<String> {
+        let mut forks = Vec::new();
+        let mut todo = vec![&self.root];
+
+        while let Some(p) = todo.pop() {
+            for c in self.children(p) {
+                forks.push(format!("{}+{}", p, c));
+                todo.push(c);
+            }
+        }
+        forks
+    }
+
+    fn write_from(&self, p: &str, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", p)?;
+
+        let children = self.children(p);
+        if !children.is_empty() {
+            write!(f, "(")?;
+            for (i, c) in children.iter().enumerate() {
+                if i > 0 {
+                    write!(f, ",")?;
+                }
+                self.write_from(c, f)?;
+            }
+            write!(f, ")")?;
+        }
+        Ok(())
+    }
+}
+
+/// `a(b(d),c)`: every process, followed by its children in brackets.
+impl fmt::Display for Tree {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.write_from(&self.root, f)
+    }
+}
+
+impl std::str::FromStr for Tree {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bad_tree = |why: &str| Err(format!("bad tree `{}`: {}", s, why));
+        let mut root = None;
+        let mut parents = BTreeMap::new();
+
+        // the open processes, innermost last, and the name being read
+        let mut stack: Vec<String> = Vec::new();
+        let mut last: Option<String> = None;
+        let mut name = String::new();
+
+        for ch in s.chars().chain(std::iter::once(',')) {
+            if !matches!(ch, '(' | ')' | ',') {
+                if !ch.is_whitespace() {
+                    name.push(ch);
+                }
+                continue;
+            }
+
+            if !name.is_empty() {
+                if name.contains(ACTION_MARKS) {
+                    return bad_tree("names cannot have the marks of actions");
+                }
+
+                match stack.last() {
+                    Some(parent) => {
+                        if name == *parent || parents.contains_key(&name) || root.as_ref() == Some(&name) {
+                            return bad_tree(&format!("{} is there twice", name));
+                        }
+                        parents.insert(name.clone(), parent.clone());
+                    },
+                    None if root.is_none() => root = Some(name.clone()),
+                    None => return bad_tree("there is more than one root"),
+                }
+                last = Some(std::mem::take(&mut name));
+            }
+
+            match ch {
+                '(' => match last.take() {
+                    Some(p) => stack.push(p),
+                    None => return bad_tree("`(` must follow a process"),
+                },
+                ')' => {
+                    if stack.pop().is_none() {
+                        return bad_tree("unbalanced `)`");
+                    }
+                    last = None;
+                },
+                _ => {},
+            }
+        }
+
+        if !stack.is_empty() {
+            return bad_tree("unbalanced `(`");
+        }
+        match root {
+            Some(root) => Ok(Tree { root, parents }),
+            None => bad_tree("it is empty"),
+        }
+    }
+}
+
+// a forker for the options showing `tree`; trees have no zombies, so
+// neither has the forker
+fn forker_at(options: &ForkerBuilder, tree: &Tree) -> Result<Forker, String> {
+    let mut forker = options.clone().action_list(Vec::new()).zombies(false).build();
+    let start = Tree::of(&forker);
+    if tree.root != start.root {
+        return Err(format!("tree {} must be rooted at {}", tree, start.root));
+    }
+
+    for fork in tree.forks() {
+        forker.apply(&fork)?;
+    }
+    Ok(forker)
+}
+
+/// Every shortest list of actions which turns tree `from` into tree `to`
+/// under `options`, which set how orphans are reparented and whether only
+/// leaves may exit; zombies are left out, as trees only hold live
+/// processes. At most `limit` lists are given, and forks only use the names
+/// in the two trees: others would have to exit again, which never makes a
+/// list shorter.
+///
+/// ```
+/// use cpu_api::quiz::{shortest_actions, Tree};
+/// use cpu_api::Forker;
+///
+/// let from: Tree = "a(b(c))".parse().unwrap();
+/// let to: Tree = "a(c)".parse().unwrap();
+/// let answers = shortest_actions(&Forker::builder(), &from, &to, 10).unwrap();
+/// assert_eq!(answers, [["b-"]]);
+/// ```
+///
+/// # Errors
+///
+/// Fails if a tree is not rooted at the root of `options` (`a` unless set
+/// with [`ForkerBuilder::root`]), or if `to` cannot be reached, or the
+/// search grows too large before reaching it.
+pub fn shortest_actions(options: &ForkerBuilder, from: &Tree, to: &Tree, limit: usize) -> Result<Vec<Vec<String>>, String> {
+    let start = forker_at(options, from)?;
+    if to.root != from.root {
+        return Err(format!("tree {} must be rooted at {}", to, from.root));
+    }
+
+    let names: BTreeSet<&String> = from.names().chain(to.names()).collect();
+
+    // breadth first, a layer at a time, keeping every way into each tree
+    // of the next layer so that all the shortest lists can be read back
+    let mut states = vec![(start, Vec::new())];
+    let mut seen: HashMap<Tree, usize> = HashMap::from([(from.clone(), 0)]);
+    let mut layer = vec![0];
+
+    while !seen.contains_key(to) {
+        if layer.is_empty() {
+            return Err(format!("no actions turn {} into {}", from, to));
+        }
+
+        // the next layer takes the states from here on
+        let next_start = states.len();
+        for &i in layer.iter() {
+            let forker = states[i].0.clone();
+            let live = forker.processes().to_vec();
+
+            let forks = live.iter().flat_map(|p| {
+                names
+                    .iter()
+                    .filter(|c| !live.contains(c))
+                    .map(move |c| format!("{}+{}", p, c))
+            });
+            let exits = live.iter().skip(1).map(|p| format!("{}-", p));
+
+            for action in forks.chain(exits) {
+                let mut after = forker.clone();
+                after.apply(&action)?;
+                let tree = Tree::of(&after);
+
+                match seen.get(&tree) {
+                    Some(&j) if j >= next_start => states[j].1.push((i, action)),
+                    Some(_) => {},
+                    None => {
+                        seen.insert(tree, states.len());
+                        states.push((after, vec![(i, action)]));
+                    },
+                }
+            }
+        }
+
+        if states.len() > MAX_STATES {
+            return Err(format!("gave up turning {} into {}: too many trees on the way", from, to));
+        }
+        layer = (next_start..states.len()).collect();
+    }
+
+    // walk back from `to` along every way in
+    let mut answers = Vec::new();
+    let mut todo = vec![(seen[to], Vec::new())];
+    while let Some((i, tail)) = todo.pop() {
+        if answers.len() == limit {
+            break;
+        }
+
+        if states[i].1.is_empty() {
+            answers.push(tail);
+            continue;
+        }
+        for (j, action) in states[i].1.iter().rev() {
+            let mut actions = vec![action.clone()];
+            actions.extend(tail.iter().cloned());
+            todo.push((*j, actions));
+        }
+    }
+    Ok(answers)
+}
+
+/// Every shortest list of actions which goes through `trees` in order, as
+/// [`shortest_actions`] finds them for each pair of neighbours. At most
+/// `limit` lists are given.
+///
+/// # Errors
+///
+/// Fails as [`shortest_actions`] does for any pair, or if there are no
+/// trees.
+pub fn shortest_actions_through(options: &ForkerBuilder, trees: &[Tree], limit: usize) -> Result<Vec<Vec<String>>, String> {
+    let Some(first) = trees.first() else {
+        return Err("no trees to go through".to_string());
+    };
+    forker_at(options, first)?;
+
+    let mut answers = vec![Vec::new()];
+    for pair in trees.windows(2) {
+        let steps = shortest_actions(options, &pair[0], &pair[1], limit)?;
+
+        answers = answers
+            .iter()
+            .flat_map(|head| {
+                steps.iter().map(move |step| {
+                    let mut actions = head.clone();
+                    actions.extend(step.iter().cloned());
+                    actions
+                })
+            })
+            .take(limit)
+            .collect();
+    }
+    Ok(answers)
+}
+
+/// Whether the trees have a single shortest list of actions through them,
+/// which makes them a fair question.
+///
+/// # Errors
+///
+/// Fails as [`shortest_actions_through`] does.
+pub fn has_unique_answer(options: &ForkerBuilder, trees: &[Tree]) -> Result<bool, String> {
+    Ok(shortest_actions_through(options, trees, 2)?.len() == 1)
+}
+
+/// Whether `answer` is a right answer to the question of how `from` became
+/// `to`: it must be legal, end at `to`, and be no longer than it has to be.
+///
+/// # Errors
+///
+/// Fails as [`shortest_actions`] does.
+pub fn check_answer(options: &ForkerBuilder, from: &Tree, to: &Tree, answer: &[String]) -> Result<bool, String> {
+    let shortest = shortest_actions(options, from, to, 1)?;
+
+    let mut forker = forker_at(options, from)?;
+    for action in answer {
+        if forker.apply(action).is_err() {
+            return Ok(false);
+        }
+    }
+    Ok(Tree::of(&forker) == *to && answer.len() == shortest[0].len())
+}
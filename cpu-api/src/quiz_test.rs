@@ -0,0 +1,153 @@
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use ostep_rng::Random;
+
+    use crate::fork::{Forker, ForkerBuilder};
+    use crate::quiz::{check_answer, has_unique_answer, shortest_actions, shortest_actions_through, Tree};
+
+    fn tree(s: &str) -> Tree {
+        s.parse().unwrap()
+    }
+
+    fn trees(list: &[&str]) -> Vec<Tree> {
+        list.iter().map(|s| tree(s)).collect()
+    }
+
+    fn answers(options: &ForkerBuilder, from: &str, to: &str) -> Vec<String> {
+        let mut found: Vec<String> = shortest_actions(options, &tree(from), &tree(to), 100)
+            .unwrap()
+            .iter()
+            .map(|actions| actions.join(","))
+            .collect();
+        found.sort();
+        found
+    }
+
+    fn actions(list: &str) -> Vec<String> {
+        list.split(',').map(str::to_string).collect()
+    }
+
+    #[test]
+    fn test_tree_parse_and_display() {
+        let t = tree(" a ( b ( d ), c )");
+        assert_eq!(t.to_string(), "a(b(d),c)");
+        assert_eq!(t.parent("b"), Some("a"));
+        assert_eq!(t.parent("a"), None);
+        assert_eq!(t.parent("x"), None);
+
+        // children in any order are the same tree
+        assert_eq!(tree("a(c,b(d))"), t);
+        assert_eq!(tree("a()"), tree("a"));
+        assert_eq!(tree("a(bb(ccc))").to_string(), "a(bb(ccc))");
+    }
+
+    #[test]
+    fn test_tree_parse_rejects_bad_trees() {
+        for bad in ["", "()", "(a)", "a,b", "a(b))", "a(b", "a(b,b)", "a(a)", "a(b(c),c)", "a(b-)", "a(b*)", "a(b=c)", "a(b^)", "a(b!)", "a(b)c"] {
+            assert!(bad.parse::<Tree>().is_err(), "`{}` parsed", bad);
+        }
+    }
+
+    #[test]
+    fn test_tree_of_forker() {
+        let mut forker = Forker::builder().action_list(actions("a+b,b+c,a+d,c+e,b-")).build();
+        forker.run(&mut io::sink()).unwrap();
+
+        assert_eq!(Tree::of(&forker), tree("a(d,c,e)"));
+    }
+
+    #[test]
+    fn test_shortest_forks() {
+        let options = Forker::builder();
+
+        assert_eq!(answers(&options, "a", "a(b(c))"), ["a+b,b+c"]);
+        assert_eq!(answers(&options, "a", "a(b,c)"), ["a+b,a+c", "a+c,a+b"]);
+        assert_eq!(answers(&options, "a(b)", "a(b)"), [""]);
+    }
+
+    #[test]
+    fn test_shortest_actions_follow_the_reparenting() {
+        let root = Forker::builder();
+        let local = Forker::builder().local_reparent(true);
+
+        // in the root mode c's exit leaves d under a, so d has to go round
+        // again
+        assert_eq!(answers(&local, "a(b(c(d)))", "a(b(d))"), ["c-"]);
+        assert_eq!(
+            answers(&root, "a(b(c(d)))", "a(b(d))"),
+            ["c-,d-,b+d", "d-,b+d,c-", "d-,c-,b+d"]
+        );
+    }
+
+    #[test]
+    fn test_shortest_actions_leaf_only() {
+        assert_eq!(answers(&Forker::builder(), "a(b(c))", "a"), ["b-,c-", "c-,b-"]);
+        assert_eq!(answers(&Forker::builder().leaf_only(true), "a(b(c))", "a"), ["c-,b-"]);
+    }
+
+    #[test]
+    fn test_shortest_actions_errors() {
+        let options = Forker::builder();
+
+        assert!(shortest_actions(&options, &tree("b"), &tree("b(c)"), 1).is_err());
+        assert!(shortest_actions(&options, &tree("a"), &tree("b"), 1).is_err());
+        assert!(shortest_actions_through(&options, &[], 1).is_err());
+
+        // the root comes from the options
+        let options = Forker::builder().root("x");
+        assert!(shortest_actions(&options, &tree("a"), &tree("a(b)"), 1).is_err());
+        assert_eq!(answers(&options, "x", "x(y)"), ["x+y"]);
+    }
+
+    #[test]
+    fn test_limit() {
+        let options = Forker::builder();
+        let found = shortest_actions(&options, &tree("a"), &tree("a(b,c,d,e)"), 5).unwrap();
+
+        assert_eq!(found.len(), 5);
+        assert!(found.iter().all(|actions| actions.len() == 4));
+    }
+
+    #[test]
+    fn test_through_trees_and_uniqueness() {
+        let options = Forker::builder();
+        let steps = trees(&["a", "a(b)", "a(b,c)", "a(c)"]);
+
+        assert_eq!(shortest_actions_through(&options, &steps, 10).unwrap(), [actions("a+b,a+c,b-")]);
+        assert!(has_unique_answer(&options, &steps).unwrap());
+        assert!(has_unique_answer(&options, &trees(&["a", "a(c)"])).unwrap());
+        assert!(!has_unique_answer(&options, &trees(&["a", "a(b,c)"])).unwrap());
+    }
+
+    #[test]
+    fn test_check_answer() {
+        let options = Forker::builder();
+        let (from, to) = (tree("a(b(c))"), tree("a(c,d)"));
+
+        assert!(check_answer(&options, &from, &to, &actions("b-,a+d")).unwrap());
+        assert!(check_answer(&options, &from, &to, &actions("a+d,b-")).unwrap());
+        // too long, wrong, illegal
+        assert!(!check_answer(&options, &from, &to, &actions("a+e,b-,a+d,e-")).unwrap());
+        assert!(!check_answer(&options, &from, &to, &actions("b-,c+d")).unwrap());
+        assert!(!check_answer(&options, &from, &to, &actions("x-,a+d")).unwrap());
+    }
+
+    #[test]
+    fn test_random_runs_are_among_the_answers() {
+        for seed in 0..20 {
+            let mut forker = Forker::builder().max_actions(6).rng(Random::python(seed)).build();
+            let run = forker.random_actions();
+
+            let mut steps = vec![Tree::of(&forker)];
+            for action in run.iter() {
+                forker.apply(action).unwrap();
+                steps.push(Tree::of(&forker));
+            }
+
+            let found = shortest_actions_through(&Forker::builder(), &steps, 1000).unwrap();
+            assert!(found.contains(&run), "seed {}: {:?} not in {:?}", seed, run, found);
+        }
+    }
+}
@@ -0,0 +1,242 @@
+//! Real runs as [`Forker`](crate::Forker) actions: a ptrace tracer which
+//! records the forks, execs and exits of a command and its descendants.
+
+use std::collections::{HashMap, HashSet};
+use std::ffi::CString;
+use std::fs;
+use std::io;
+use std::ptr;
+use std::thread;
+use std::time::Duration;
+
+use libc::{c_void, pid_t};
+
+// the type of ptrace requests, which glibc makes unsigned
+#[cfg(target_env = "gnu")]
+type Request = libc::c_uint;
+#[cfg(not(target_env = "gnu"))]
+type Request = libc::c_int;
+
+// how many one-letter names there are, as `Forker` gives them out
+const LETTERS: usize = 52;
+
+// the `n`th name `Forker` gives out: a, ..., Z, then aa, ..., ZZ, then aaa
+pub(crate) fn name(mut n: usize) -> String {
+    let letters: Vec<char> = ('a'..='z').chain('A'..='Z').collect();
+
+    let (mut len, mut count) = (1, LETTERS);
+    while n >= count {
+        n -= count;
+        len += 1;
+        count *= LETTERS;
+    }
+
+    let mut name = vec!['a'; len];
+    for ch in name.iter_mut().rev() {
+        *ch = letters[n % LETTERS];
+        n /= LETTERS;
+    }
+    name.into_iter().collect()
+}
+
+fn ptrace(request: Request, pid: pid_t, data: usize) -> io::Result<()> {
+    // SAFETY: the requests used here take no address, and data is a plain
+    // number or points to memory of the tracer
+    match unsafe { libc::ptrace(request, pid, ptr::null_mut::<c_void>(), data as *mut c_void) } {
+        -1 => Err(io::Error::last_os_error()),
+        _ => Ok(()),
+    }
+}
+
+// the tracees, named as `Forker` names processes, and what they did
+struct Tracer {
+    names: HashMap<pid_t, String>,
+    // past the stop every tracee starts with
+    started: HashSet<pid_t>,
+    // every tracee whose death has not been waited for yet
+    alive: HashSet<pid_t>,
+    root: pid_t,
+    forks: usize,
+    actions: Vec<String>,
+}
+
+impl Tracer {
+    fn new(root: pid_t) -> Self {
+        Tracer {
+            names: HashMap::from([(root, name(0))]),
+            started: HashSet::from([root]),
+            alive: HashSet::from([root]),
+            root,
+            forks: 0,
+            actions: Vec::new(),
+        }
+    }
+
+    fn run(mut self) -> io::Result<Vec<String>> {
+        let options = libc::PTRACE_O_TRACEFORK
+            | libc::PTRACE_O_TRACEVFORK
+            | libc::PTRACE_O_TRACEEXEC
+            | libc::PTRACE_O_TRACEEXIT
+            | libc::PTRACE_O_EXITKILL;
+        ptrace(libc::PTRACE_SETOPTIONS, self.root, options as usize)?;
+        ptrace(libc::PTRACE_CONT, self.root, 0)?;
+
+        while !self.alive.is_empty() {
+            let Some((pid, status)) = self.poll()? else {
+                thread::sleep(Duration::from_millis(1));
+                continue;
+            };
+
+            if libc::WIFEXITED(status) || libc::WIFSIGNALED(status) {
+                self.exit(pid);
+                self.alive.remove(&pid);
+                continue;
+            }
+            if !libc::WIFSTOPPED(status) {
+                continue;
+            }
+
+            let signal = libc::WSTOPSIG(status);
+            let mut inject = 0;
+            match status >> 16 {
+                libc::PTRACE_EVENT_FORK | libc::PTRACE_EVENT_VFORK => self.fork(pid)?,
+                libc::PTRACE_EVENT_EXEC => self.exec(pid),
+                libc::PTRACE_EVENT_EXIT => self.exit(pid),
+                _ if signal == libc::SIGSTOP && !self.started.contains(&pid) => {
+                    self.started.insert(pid);
+                },
+                _ => inject = signal,
+            }
+
+            // the tracee may have been killed since it stopped
+            match ptrace(libc::PTRACE_CONT, pid, inject as usize) {
+                Err(e) if e.raw_os_error() == Some(libc::ESRCH) => {},
+                result => result?,
+            }
+        }
+        Ok(self.actions)
+    }
+
+    // the next tracee with a change of state, if any has one now; only the
+    // tracees are waited for, so that other children of the caller keep
+    // their exit statuses for it
+    fn poll(&mut self) -> io::Result<Option<(pid_t, i32)>> {
+        for pid in self.alive.iter().copied().collect::<Vec<pid_t>>() {
+            let mut status = 0;
+            // SAFETY: status is a plain int
+            match unsafe { libc::waitpid(pid, &mut status, libc::__WALL | libc::WNOHANG) } {
+                0 => {},
+                -1 => {
+                    let e = io::Error::last_os_error();
+                    match e.raw_os_error() {
+                        // no longer ours to wait for: it is gone
+                        Some(libc::ECHILD) => {
+                            self.exit(pid);
+                            self.alive.remove(&pid);
+                        },
+                        Some(libc::EINTR) => {},
+                        _ => return Err(e),
+                    }
+                },
+                _ => return Ok(Some((pid, status))),
+            }
+        }
+        Ok(None)
+    }
+
+    fn fork(&mut self, parent: pid_t) -> io::Result<()> {
+        let mut child: libc::c_ulong = 0;
+        ptrace(libc::PTRACE_GETEVENTMSG, parent, &mut child as *mut libc::c_ulong as usize)?;
+        let child = child as pid_t;
+
+        self.forks += 1;
+        self.names.insert(child, name(self.forks));
+        self.actions.push(format!("{}+{}", self.names[&parent], self.names[&child]));
+
+        // its start stop is waited for from now on, like all it does
+        self.alive.insert(child);
+        Ok(())
+    }
+
+    fn exec(&mut self, pid: pid_t) {
+        // a comma would end the action
+        let comm = fs::read_to_string(format!("/proc/{}/comm", pid)).unwrap_or_default();
+        let program = match comm.trim_end() {
+            "" => "?".to_string(),
+            comm => comm.replace(',', "_"),
+        };
+        self.actions.push(format!("{}={}", self.names[&pid], program));
+    }
+
+    // the exit stop comes first, then the death itself, which is left
+    // alone; so is the exit of the root, which never exits in the model
+    fn exit(&mut self, pid: pid_t) {
+        if let Some(name) = self.names.remove(&pid) {
+            if pid != self.root {
+                self.actions.push(format!("{}-", name));
+            }
+        }
+    }
+}
+
+/// Run `command`, a program and its arguments, under ptrace, and give what
+/// it and all its descendants did as [`Forker`](crate::Forker) actions:
+/// the command is `a`, forks are `X+Y`, execs `X=P` with the command name
+/// of the new program, and exits `X-`. The exit of `a` is left out. The
+/// command's output goes to stderr, so that it stays apart from the trees.
+///
+/// On an exit, Linux gives the orphans to the nearest subreaper or init,
+/// which the subreaper mode of the replay, with `a` for init, follows best.
+///
+/// Only the traced processes are waited for, so the other children of the
+/// calling process are left for it to wait for.
+///
+/// # Errors
+///
+/// Fails if the command is empty, cannot be run or cannot be traced.
+pub fn trace_command(command: &[String]) -> Result<Vec<String>, String> {
+    let args = command
+        .iter()
+        .map(|arg| CString::new(arg.as_str()))
+        .collect::<Result<Vec<CString>, _>>()
+        .map_err(|_| format!("bad command `{}`: arguments cannot hold NUL", command.join(" ")))?;
+    if args.is_empty() {
+        return Err("no command to trace".to_string());
+    }
+    let mut argv: Vec<*const libc::c_char> = args.iter().map(|arg| arg.as_ptr()).collect();
+    argv.push(ptr::null());
+
+    // SAFETY: the child makes only async-signal-safe calls until it execs
+    let root = unsafe { libc::fork() };
+    match root {
+        -1 => return Err(format!("cannot fork: {}", io::Error::last_os_error())),
+        0 => unsafe {
+            libc::ptrace(libc::PTRACE_TRACEME, 0, ptr::null_mut::<c_void>(), ptr::null_mut::<c_void>());
+            libc::dup2(2, 1);
+            libc::raise(libc::SIGSTOP);
+            libc::execvp(argv[0], argv.as_ptr());
+            libc::_exit(127);
+        },
+        _ => {},
+    }
+
+    // the child stops before it execs, traced or not
+    let mut status = 0;
+    // SAFETY: status is a plain int
+    unsafe { libc::waitpid(root, &mut status, libc::__WALL | libc::WUNTRACED) };
+
+    let actions = Tracer::new(root).run().map_err(|e| {
+        // SAFETY: root is our own child
+        unsafe {
+            libc::kill(root, libc::SIGKILL);
+            libc::waitpid(root, &mut status, libc::__WALL);
+        }
+        format!("cannot trace `{}`: {}", command.join(" "), e)
+    })?;
+
+    // without an exec of `a`, the command never ran
+    match actions.first() {
+        Some(action) if action.starts_with("a=") => Ok(actions),
+        _ => Err(format!("cannot run `{}`", command.join(" "))),
+    }
+}
@@ -0,0 +1,36 @@
+#[cfg(test)]
+mod tests {
+    use std::process::Command;
+
+    use crate::fork::Forker;
+    use crate::trace::{name, trace_command};
+
+    #[test]
+    fn test_names_follow_the_forker() {
+        assert_eq!(name(0), "a");
+        assert_eq!(name(25), "z");
+        assert_eq!(name(51), "Z");
+        assert_eq!(name(52), "aa");
+        assert_eq!(name(53), "ab");
+        assert_eq!(name(52 + 52 * 52 - 1), "ZZ");
+        assert_eq!(name(52 + 52 * 52), "aaa");
+
+        // as the random actions name their forks
+        let mut forker = Forker::builder().max_actions(60).fork_percentage(1.0).build();
+        let forks = forker.random_actions();
+        for (i, fork) in forks.iter().enumerate() {
+            assert!(fork.ends_with(&format!("+{}", name(i + 1))), "{}", fork);
+        }
+    }
+
+    #[test]
+    fn test_trace_leaves_other_children_alone() {
+        let mut other = Command::new("sh").args(["-c", "exit 3"]).spawn().unwrap();
+
+        let actions = trace_command(&["sh".to_string(), "-c".to_string(), "sleep 0.1".to_string()]).unwrap();
+        assert_eq!(actions[0], "a=sh");
+
+        // its exit status is still there to wait for
+        assert_eq!(other.wait().unwrap().code(), Some(3));
+    }
+}
@@ -0,0 +1,13 @@
+#![no_main]
+
+use cpu_intro::Scheduler;
+use libfuzzer_sys::fuzz_target;
+
+// a `-l` process description such as `5:50`
+fuzz_target!(|program: &str| {
+    let mut s = Scheduler::new();
+    if s.load(program).is_ok() {
+        s.set_trace(false);
+        s.run(&mut std::io::sink()).unwrap();
+    }
+});
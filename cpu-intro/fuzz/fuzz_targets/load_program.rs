@@ -0,0 +1,13 @@
+#![no_main]
+
+use cpu_intro::Scheduler;
+use libfuzzer_sys::fuzz_target;
+
+// a `-P` program: parse it, and run whatever parses
+fuzz_target!(|program: &str| {
+    let mut s = Scheduler::new();
+    if s.load_program(program).is_ok() {
+        s.set_trace(false);
+        s.run(&mut std::io::sink()).unwrap();
+    }
+});
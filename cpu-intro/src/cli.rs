@@ -0,0 +1,201 @@
+//! The `process-run.py` command line, shared by the `cpu-intro` binary and
+//! `ostep process-run`.
+
+use std::io::{self, Write};
+
+use clap::{Args, Subcommand};
+
+use crate::process_run::{Instruction, IoDoneBehavior, RunStats, Scheduler, SwitchBehavior, MAX_IO_LENGTH};
+use crate::sweep::{Axis, Sweep, Workload};
+
+/// Every option of `process-run.py` but the seed and `-c`, which the
+/// binaries share with the other tools.
+#[derive(Args, Debug)]
+pub struct Options {
+    /// more specific controls over programs, e.g. `c7,i,f(c2),w:c3`
+    /// (c<n>: compute for n, i: issue an io, f(...): fork a child running
+    /// the code in brackets, w: wait for a child, x: exit, l<name>: acquire
+    /// a lock, u<name>: release a lock, s: make a system call)
+    #[arg(short = 'P', long, default_value = "")]
+    pub program: String,
+
+    /// a comma-separated list of processes to run, in the form X1:Y1,X2:Y2,...
+    /// where X is the number of instructions that process should run, and Y
+    /// the chances (from 0 to 100) that an instruction will use the CPU
+    #[arg(short = 'l', long = "processlist", default_value = "")]
+    pub process_list: String,
+
+    /// how long an IO takes
+    #[arg(short = 'L', long = "iolength", default_value_t = 5)]
+    pub io_length: i32,
+
+    /// how long a system call spends in kernel mode
+    #[arg(short = 'K', long = "syscalllength", default_value_t = 1)]
+    pub syscall_length: i32,
+
+    /// when to switch between processes: SWITCH_ON_IO, SWITCH_ON_END
+    #[arg(short = 'S', long = "switch", default_value = "SWITCH_ON_IO")]
+    pub process_switch_behavior: SwitchBehavior,
+
+    /// type of behavior when IO ends: IO_RUN_LATER, IO_RUN_IMMEDIATE
+    #[arg(short = 'I', long = "iodone", default_value = "IO_RUN_LATER")]
+    pub io_done_behavior: IoDoneBehavior,
+
+    /// explain the reason for every scheduling decision; only useful with -c flag
+    #[arg(short = 'e', long)]
+    pub explain: bool,
+
+    /// do not print the trace, only run the simulation; with -p this gives
+    /// quick statistics for long simulations
+    #[arg(short = 'q', long)]
+    pub quiet: bool,
+
+    /// print statistics at end; only useful with -c flag
+    #[arg(short = 'p', long = "printstats")]
+    pub print_stats: bool,
+
+    /// sweep the workload over parameters instead of running it once
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+/// Subcommands of `process-run`.
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// run the workload once per point of a parameter range and print the
+    /// statistics as CSV, e.g. `-l 5:50,5:50 sweep --vary io_length=1:20`
+    Sweep {
+        /// `param=start:end[:step]` where param is io_length, percent (the y
+        /// of every x:y), procs (copies of the first process) or seed;
+        /// give it twice to sweep a grid
+        #[arg(long, required = true)]
+        vary: Vec<Axis>,
+    },
+}
+
+fn print_code<W: Write>(out: &mut W, code: &[Instruction], indent: usize) -> io::Result<()> {
+    for inst in code {
+        writeln!(out, "{:indent$}{}", "", inst, indent = indent)?;
+
+        if let Instruction::Fork(child) = inst {
+            print_code(out, child, indent + 2)?;
+        }
+    }
+    Ok(())
+}
+
+/// Do what `process-run.py` does with these options: print the processes
+/// as a question, or with `solve` the trace which answers it.
+///
+/// # Errors
+///
+/// Fails on options which make no sense and on programs which do not load.
+pub fn run<W: Write>(options: &Options, seed: u64, solve: bool, out: &mut W) -> Result<(), String> {
+    if !(0..=MAX_IO_LENGTH).contains(&options.io_length) {
+        return Err(format!("io length must be between 0 and {}", MAX_IO_LENGTH));
+    }
+
+    if options.syscall_length < 1 {
+        return Err("syscall length must be at least 1".to_string());
+    }
+
+    if options.program.is_empty() && options.process_list.is_empty() {
+        return Err("no processes given: use -l or -P".to_string());
+    }
+
+    let workload = Workload {
+        program: options.program.clone(),
+        process_list: options.process_list.clone(),
+        io_length: options.io_length,
+        syscall_length: options.syscall_length,
+        process_switch_behavior: options.process_switch_behavior,
+        io_done_behavior: options.io_done_behavior,
+        seed,
+    };
+
+    if let Some(Command::Sweep { vary }) = &options.command {
+        let sweep = Sweep::new(workload, vary.clone())?;
+        return sweep.run(out).map_err(|e| format!("Failed to write sweep: {}", e));
+    }
+
+    let mut s = workload.scheduler()?;
+    s.set_explain(options.explain);
+    s.set_trace(!options.quiet);
+
+    let write_failed = |e: io::Error| format!("Failed to write trace: {}", e);
+
+    if !solve {
+        return print_question(out, &s, options).map_err(write_failed);
+    }
+
+    // a run too long for the clock is not a failed write
+    let stats = s.run(out).map_err(|e| match e.kind() {
+        io::ErrorKind::InvalidInput => e.to_string(),
+        _ => write_failed(e),
+    })?;
+
+    if options.print_stats {
+        print_stats(out, &s, &stats).map_err(write_failed)?;
+    }
+    Ok(())
+}
+
+fn print_question<W: Write>(out: &mut W, s: &Scheduler, options: &Options) -> io::Result<()> {
+    writeln!(out, "Produce a trace of what would happen when you run these processes:")?;
+    for pid in 0..s.get_num_processes() as i32 {
+        writeln!(out, "Process {}", pid)?;
+        for idx in 0..s.get_num_instructions(pid) {
+            let inst = s.get_instruction(pid, idx);
+            writeln!(out, "  {}", inst)?;
+
+            if let Instruction::Fork(child) = inst {
+                print_code(out, child, 4)?;
+            }
+        }
+        writeln!(out)?;
+    }
+
+    writeln!(out, "Important behaviors:")?;
+    match options.process_switch_behavior {
+        SwitchBehavior::SwitchOnIo => {
+            writeln!(out, "  System will switch when the current process is FINISHED or ISSUES AN IO")?
+        },
+        SwitchBehavior::SwitchOnEnd => {
+            writeln!(out, "  System will switch when the current process is FINISHED")?
+        },
+    }
+    match options.io_done_behavior {
+        IoDoneBehavior::IoRunImmediate => {
+            writeln!(out, "  After IOs, the process issuing the IO will run IMMEDIATELY")?
+        },
+        IoDoneBehavior::IoRunLater => {
+            writeln!(out, "  After IOs, the process issuing the IO will run LATER (when it is its turn)")?
+        },
+    }
+    writeln!(out)
+}
+
+fn print_stats<W: Write>(out: &mut W, s: &Scheduler, stats: &RunStats) -> io::Result<()> {
+    let total = stats.total_time as f64;
+    writeln!(out)?;
+    writeln!(out, "Stats: Total Time {}", stats.total_time)?;
+    writeln!(out, "Stats: CPU Busy {} ({:.2}%)", stats.cpu_busy, 100.0 * stats.cpu_busy as f64 / total)?;
+    writeln!(out, "Stats: IO Busy  {} ({:.2}%)", stats.io_busy, 100.0 * stats.io_busy as f64 / total)?;
+
+    // like `time`: how the busy cpu splits between user and kernel mode
+    if stats.kernel_time > 0 {
+        writeln!(out, "Stats: User Time   {}", stats.user_time)?;
+        writeln!(out, "Stats: Kernel Time {}", stats.kernel_time)?;
+        for pid in 0..s.get_num_processes() as i32 {
+            let p = s.process(pid).unwrap();
+            writeln!(
+                out,
+                "Stats: PID {:>2} user {} kernel {}",
+                pid,
+                p.user_time(),
+                p.kernel_time()
+            )?;
+        }
+    }
+    writeln!(out)
+}
@@ -0,0 +1,57 @@
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use crate::cli::{self, Options};
+
+    #[derive(Parser)]
+    struct Args {
+        #[command(flatten)]
+        options: Options,
+    }
+
+    fn run(args: &[&str], seed: u64, solve: bool) -> Result<String, String> {
+        let args = Args::try_parse_from([&["process-run"], args].concat()).unwrap();
+        let mut out = Vec::new();
+        cli::run(&args.options, seed, solve, &mut out)?;
+        Ok(String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn test_question_and_answer() {
+        let question = run(&["-P", "c1,f(c2),w"], 0, false).unwrap();
+        assert!(question.starts_with("Produce a trace"));
+        assert!(question.contains("  fork\n    cpu\n    cpu\n  wait\n"));
+
+        let answer = run(&["-P", "c1,i", "-L", "2", "-p"], 0, true).unwrap();
+        assert!(answer.starts_with("Time"));
+        assert!(answer.contains("Stats: Total Time 5"));
+    }
+
+    #[test]
+    fn test_seed_picks_the_workload() {
+        let first = run(&["-l", "10:50"], 1, false).unwrap();
+        assert_eq!(run(&["-l", "10:50"], 1, false).unwrap(), first);
+        assert_ne!(run(&["-l", "10:50"], 2, false).unwrap(), first);
+    }
+
+    #[test]
+    fn test_bad_options_are_errors() {
+        assert!(run(&[], 0, true).is_err());
+        assert!(run(&["-l", "5:50", "--iolength=-1"], 0, true).is_err());
+        assert!(run(&["-l", "5:50", "-L", "2147483647"], 0, true).is_err());
+        assert!(run(&["-l", "5:50", "-K", "0"], 0, true).is_err());
+        assert!(run(&["-P", "c1,,i"], 0, true).is_err());
+        assert!(run(&["-P", "c1", "sweep", "--vary", "percent=0:100"], 0, true).is_err());
+    }
+
+    #[test]
+    fn test_long_io_length_does_not_overflow() {
+        let answer = run(&["-l", "2:0", "-L", "1000000000", "-q", "-p"], 0, true).unwrap();
+        assert!(answer.contains("Stats: Total Time 2000000004"));
+
+        // a third io would end past the last tick the clock can count
+        let error = run(&["-l", "3:0", "-L", "1000000000", "-q", "-p"], 0, true).unwrap_err();
+        assert_eq!(error, "the clock overflows before the I/O ends");
+    }
+}
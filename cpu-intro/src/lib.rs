@@ -0,0 +1,21 @@
+//! A port of `process-run.py` from the OSTEP homework: a simulator of
+//! processes sharing one cpu while they compute, do I/O, fork, wait, take
+//! locks and make system calls.
+//!
+//! [`Scheduler`] loads the processes and runs them, printing a trace of
+//! every tick; [`sweep::Sweep`] runs a workload over a range of parameters.
+
+#![warn(missing_docs)]
+
+pub mod cli;
+pub mod process_run;
+pub mod sweep;
+
+mod cli_test;
+mod process_run_test;
+mod process_run_prop_test;
+mod sweep_test;
+
+pub use process_run::{
+    Instruction, IoDoneBehavior, ProcessState, RunStats, Scheduler, SwitchBehavior,
+};
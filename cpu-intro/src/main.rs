@@ -1,7 +1,27 @@
-mod process_run;
-mod seed;
-mod process_run_test;
+use clap::Parser;
+
+use cpu_intro::cli::{self, Options};
+
+#[derive(Parser, Debug)]
+#[command(about = "Simulate how processes use the CPU and issue I/Os")]
+struct Args {
+    /// the random seed
+    #[arg(short = 's', long, default_value_t = 0)]
+    seed: u64,
+
+    /// compute answers for me
+    #[arg(short = 'c')]
+    solve: bool,
+
+    #[command(flatten)]
+    options: Options,
+}
 
 fn main() {
-    println!("Hello, world!");
+    let args = Args::parse();
+
+    if let Err(e) = cli::run(&args.options, args.seed, args.solve, &mut std::io::stdout()) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
 }
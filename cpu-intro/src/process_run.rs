@@ -1,41 +1,356 @@
-use std::collections::HashMap;
+//! The scheduler, its processes and the `-P` program syntax.
 
-use crate::{push_process, assign_state, seed};
+use std::cmp::{Reverse, min};
+use std::collections::{BinaryHeap, HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::io::{self, Write};
+use std::str::FromStr;
 
-const DO_COMPUTE: &'static str = "cpu";
-const DO_IO: &'static str = "io";
-const DO_IO_DONE: &'static str = "io_done";
+use ostep_rng::Random;
 
+const DO_COMPUTE: &str = "cpu";
+const DO_IO: &str = "io";
+const DO_IO_DONE: &str = "io_done";
+const DO_FORK: &str = "fork";
+const DO_WAIT: &str = "wait";
+const DO_EXIT: &str = "exit";
+const DO_LOCK: &str = "lock";
+const DO_UNLOCK: &str = "unlock";
+const DO_SYSCALL: &str = "syscall";
+const DO_KERNEL: &str = "kernel";
+
+// limits on what a program may ask for, so untrusted input cannot
+// exhaust the memory or the stack
+const MAX_INSTRUCTIONS: usize = 1 << 20;
+const MAX_FORK_DEPTH: usize = 32;
+
+const SWITCH_ON_IO: &str = "SWITCH_ON_IO";
+const SWITCH_ON_END: &str = "SWITCH_ON_END";
+const IO_RUN_LATER: &str = "IO_RUN_LATER";
+const IO_RUN_IMMEDIATE: &str = "IO_RUN_IMMEDIATE";
+
+/// Where a process is in its life, printed in the trace as
+/// `RUNNING`, `READY`, `BLOCKED` and `DONE`.
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub enum ProcessState {
+    /// On the cpu this tick.
     Running,
+    /// Waiting for the cpu.
     Ready,
+    /// Waiting for an I/O, a child or a lock.
     Blocked,
+    /// Ran to the end of its code or exited.
     Terminated,
 }
 
+impl fmt::Display for ProcessState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let state = match self {
+            ProcessState::Running => "RUNNING",
+            ProcessState::Ready => "READY",
+            ProcessState::Blocked => "BLOCKED",
+            ProcessState::Terminated => "DONE",
+        };
+        f.pad(state)
+    }
+}
+
+/// When to switch between processes, parsed from `SWITCH_ON_IO` or
+/// `SWITCH_ON_END`.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum SwitchBehavior {
+    /// Switch when the running process finishes or issues an I/O.
+    SwitchOnIo,
+    /// Switch only when the running process finishes.
+    SwitchOnEnd,
+}
+
+impl FromStr for SwitchBehavior {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            SWITCH_ON_IO => Ok(SwitchBehavior::SwitchOnIo),
+            SWITCH_ON_END => Ok(SwitchBehavior::SwitchOnEnd),
+            _ => Err(format!(
+                "bad switch behavior `{}`: must be {} or {}",
+                s, SWITCH_ON_IO, SWITCH_ON_END
+            )),
+        }
+    }
+}
+
+/// What to do with a process once its I/O has completed, parsed from
+/// `IO_RUN_LATER` or `IO_RUN_IMMEDIATE`.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum IoDoneBehavior {
+    /// The process becomes ready and waits for its turn.
+    IoRunLater,
+    /// The process preempts whoever is running.
+    IoRunImmediate,
+}
+
+impl FromStr for IoDoneBehavior {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            IO_RUN_LATER => Ok(IoDoneBehavior::IoRunLater),
+            IO_RUN_IMMEDIATE => Ok(IoDoneBehavior::IoRunImmediate),
+            _ => Err(format!(
+                "bad io done behavior `{}`: must be {} or {}",
+                s, IO_RUN_LATER, IO_RUN_IMMEDIATE
+            )),
+        }
+    }
+}
+
+/// Things a process can do, one per tick of the cpu.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Instruction {
+    /// Use the cpu for a tick.
+    Compute,
+    /// Issue an I/O and block until it completes.
+    Io,
+    /// Handle the completion of the previous I/O.
+    IoDone,
+    /// Create a child process which runs the given code.
+    Fork(Vec<Instruction>),
+    /// Block until a child process terminates.
+    Wait,
+    /// Terminate right away, skipping the rest of the code.
+    Exit,
+    /// Acquire the named lock, blocking while another process holds it.
+    Lock(String),
+    /// Release the named lock, handing it to the first waiter if any.
+    Unlock(String),
+    /// Trap into the kernel, which then works for the syscall length.
+    Syscall,
+    /// Kernel work on behalf of a syscall; never loaded from a program.
+    Kernel,
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Instruction::Compute => DO_COMPUTE.to_string(),
+            Instruction::Io => DO_IO.to_string(),
+            Instruction::IoDone => DO_IO_DONE.to_string(),
+            Instruction::Fork(_) => DO_FORK.to_string(),
+            Instruction::Wait => DO_WAIT.to_string(),
+            Instruction::Exit => DO_EXIT.to_string(),
+            Instruction::Lock(lock) => format!("{}({})", DO_LOCK, lock),
+            Instruction::Unlock(lock) => format!("{}({})", DO_UNLOCK, lock),
+            Instruction::Syscall => DO_SYSCALL.to_string(),
+            Instruction::Kernel => DO_KERNEL.to_string(),
+        };
+        f.pad(&name)
+    }
+}
+
+/// A process as the scheduler sees it.
 pub struct ProcessStructure {
-    pub proc_id: i32,
-    pub proc_pc: usize,
-    pub proc_code: Vec<&'static str>, // should find better way to do this
-    pub proc_state: ProcessState,
+    /// Pid, in the order the processes were created.
+    proc_id: i32,
+    /// Index of the next instruction to run.
+    proc_pc: usize,
+    /// The code the process runs.
+    proc_code: Vec<Instruction>,
+    /// Current state.
+    proc_state: ProcessState,
+    /// Pid of the process which forked this one, if any.
+    proc_parent: Option<i32>,
+    /// Set once the parent has waited for this (terminated) process.
+    proc_reaped: bool,
+    /// Ticks of kernel work left for the syscall in progress.
+    proc_kernel_left: i32,
+    /// Ticks spent on the cpu in user mode.
+    proc_user_time: i32,
+    /// Ticks spent on the cpu in kernel mode.
+    proc_kernel_time: i32,
+}
+
+impl ProcessStructure {
+    /// Pid, in the order the processes were created.
+    pub fn pid(&self) -> i32 {
+        self.proc_id
+    }
+
+    /// Index of the next instruction to run.
+    pub fn pc(&self) -> usize {
+        self.proc_pc
+    }
+
+    /// The code the process runs.
+    pub fn code(&self) -> &[Instruction] {
+        &self.proc_code
+    }
+
+    /// Current state.
+    pub fn state(&self) -> ProcessState {
+        self.proc_state
+    }
+
+    /// Pid of the process which forked this one, if any.
+    pub fn parent(&self) -> Option<i32> {
+        self.proc_parent
+    }
+
+    /// Whether the parent has waited for this (terminated) process.
+    pub fn is_reaped(&self) -> bool {
+        self.proc_reaped
+    }
+
+    /// Ticks of kernel work left for the syscall in progress.
+    pub fn kernel_left(&self) -> i32 {
+        self.proc_kernel_left
+    }
+
+    /// Ticks spent on the cpu in user mode.
+    pub fn user_time(&self) -> i32 {
+        self.proc_user_time
+    }
+
+    /// Ticks spent on the cpu in kernel mode.
+    pub fn kernel_time(&self) -> i32 {
+        self.proc_kernel_time
+    }
 }
 
+/// What a run of the scheduler added up to, in ticks.
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+pub struct RunStats {
+    /// Ticks in which some process used the cpu.
+    pub cpu_busy: i32,
+    /// Ticks in which at least one I/O was in flight.
+    pub io_busy: i32,
+    /// Ticks until every process terminated (or got stuck).
+    pub total_time: i32,
+    /// The part of `cpu_busy` spent in user mode.
+    pub user_time: i32,
+    /// The part of `cpu_busy` spent in kernel mode.
+    pub kernel_time: i32,
+}
+
+/// Runs processes on a single cpu and prints what happens in every tick,
+/// like `process-run.py -c`.
+///
+/// ```
+/// use cpu_intro::Scheduler;
+///
+/// let mut s = Scheduler::new();
+/// s.load_program("c2,i,c1").unwrap();
+///
+/// let mut trace = Vec::new();
+/// let stats = s.run(&mut trace).unwrap();
+/// assert_eq!(stats.cpu_busy, 5);
+/// ```
 pub struct Scheduler {
-    pub proc_info: HashMap<i32, ProcessStructure>,
-    pub curr_proc: i32,
-    io_finish_time: HashMap<i32, i32>,
+    // every process loaded or forked so far, by pid
+    proc_info: HashMap<i32, ProcessStructure>,
+    curr_proc: i32,
+    process_switch_behavior: SwitchBehavior,
+    io_done_behavior: IoDoneBehavior,
+    io_length: i32,
+    syscall_length: i32,
+    // print the cpu mode column, only done when some process has syscalls
+    show_modes: bool,
+    // annotate every tick with the reasons for the decisions taken in it
+    explain: bool,
+    notes: Vec<String>,
+    // print the per-tick trace; without it only the statistics are kept
+    trace: bool,
+    // pending io completions as (finish time, pid), earliest first
+    io_done_events: BinaryHeap<Reverse<(i32, i32)>>,
+    // processes blocked in `wait` until one of their children terminates
+    waiting: HashSet<i32>,
+    // current holder of each named lock, and who is queued behind it
+    lock_holder: HashMap<String, i32>,
+    lock_waiters: HashMap<String, VecDeque<i32>>,
+    // draws the instructions of `x:y` descriptions
+    rng: Random,
+}
+
+/// The longest I/O a scheduler takes, which leaves the clock room to see
+/// one I/O end.
+pub const MAX_IO_LENGTH: i32 = 1_000_000_000;
+
+impl Default for Scheduler {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Scheduler {
+    /// A scheduler which switches on I/O, runs finished I/Os later and
+    /// takes 5 ticks per I/O, the defaults of `process-run.py`.
     pub fn new() -> Self {
+        Self::with_behavior(SwitchBehavior::SwitchOnIo, IoDoneBehavior::IoRunLater, 5)
+    }
+
+    /// A scheduler with the given behaviors and I/O length.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `io_length` is negative or longer than [`MAX_IO_LENGTH`].
+    pub fn with_behavior(
+        process_switch_behavior: SwitchBehavior,
+        io_done_behavior: IoDoneBehavior,
+        io_length: i32,
+    ) -> Self {
+        assert!(
+            (0..=MAX_IO_LENGTH).contains(&io_length),
+            "io length must be between 0 and {}",
+            MAX_IO_LENGTH
+        );
         Self {
             proc_info: HashMap::new(),
             curr_proc: 0,
-            io_finish_time: HashMap::new(),
+            process_switch_behavior,
+            io_done_behavior,
+            io_length,
+            syscall_length: 1,
+            show_modes: false,
+            explain: false,
+            notes: Vec::new(),
+            trace: true,
+            io_done_events: BinaryHeap::new(),
+            waiting: HashSet::new(),
+            lock_holder: HashMap::new(),
+            lock_waiters: HashMap::new(),
+            rng: Random::default(),
         }
     }
 
+    /// The random stream [`Scheduler::load`] draws from; a new scheduler
+    /// starts from seed 0.
+    pub fn set_rng(&mut self, rng: Random) {
+        self.rng = rng;
+    }
+
+    /// How many ticks a syscall spends in kernel mode, trap included.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `syscall_length` is less than 1.
+    pub fn set_syscall_length(&mut self, syscall_length: i32) {
+        assert!(syscall_length >= 1, "syscall length must be at least 1");
+        self.syscall_length = syscall_length;
+    }
+
+    /// Annotate every tick of the trace with the reasons for the decisions
+    /// taken in it.
+    pub fn set_explain(&mut self, explain: bool) {
+        self.explain = explain;
+    }
+
+    /// Print the per-tick trace; without it `run` only keeps the statistics
+    /// and jumps over idle time, which makes long simulations fast.
+    pub fn set_trace(&mut self, trace: bool) {
+        self.trace = trace;
+    }
+
+    /// Add a ready process with no code and return its pid.
     pub fn new_process(&mut self) -> i32 {
         let proc_id = self.proc_info.len() as i32;
         let proc_info = ProcessStructure {
@@ -43,60 +358,111 @@ impl Scheduler {
             proc_pc: 0,
             proc_code: Vec::new(),
             proc_state: ProcessState::Ready,
+            proc_parent: None,
+            proc_reaped: false,
+            proc_kernel_left: 0,
+            proc_user_time: 0,
+            proc_kernel_time: 0,
         };
         self.proc_info.insert(proc_id, proc_info);
         proc_id
-    } 
+    }
 
-    pub fn load_program(&mut self, program: &str) {
+    /// Add a process running `program` and return its pid.
+    ///
+    /// A program looks like this:
+    ///
+    /// ```text
+    /// c7,i,c1,f(c2,i),w,x
+    /// ```
+    ///
+    /// which means compute for 7, then i/o, then compute for 1, then fork
+    /// a child which computes for 2 and does an i/o, then wait for that
+    /// child to terminate, then exit.
+    ///
+    /// `lm` and `um` acquire and release the lock named `m`, and `s` makes a
+    /// system call.
+    ///
+    /// # Errors
+    ///
+    /// Fails without adding a process if the program does not parse, is
+    /// longer than a million instructions or nests forks too deep.
+    pub fn load_program(&mut self, program: &str) -> Result<i32, String> {
+        let mut budget = MAX_INSTRUCTIONS;
+        let code = Self::parse_code(program, 0, &mut budget)?;
         let proc_id = self.new_process();
 
-        for p in program.split(',') {
-            let opcode = p.chars().nth(0).unwrap();
-            self.check_opcode(opcode, p, proc_id);
+        for instruction in code {
+            push_process!(self.proc_info, proc_id, instruction);
         }
+        Ok(proc_id)
     }
 
-    pub fn load(&mut self, program: &str) {
-        let proc_id = self.new_process();
-
+    /// Add a process described as `x:y`, that is `x` instructions which use
+    /// the cpu with a `y` percent chance each and issue an I/O otherwise, and
+    /// return its pid. The instructions are drawn from the scheduler's
+    /// random stream, see [`Scheduler::set_rng`].
+    ///
+    /// # Errors
+    ///
+    /// Fails without adding a process if the description does not parse or
+    /// asks for too many instructions.
+    pub fn load(&mut self, program: &str) -> Result<i32, String> {
         // program is a string of colon-separated instructions (like "5:100")
         // which denotes `5` compute instructions and `100` cpu chances.
-        let tmp = program.split(':').collect::<Vec<&str>>();
-        if tmp.len() != 2 {
-            panic!("Bad description `{}`: Must be integer `x:y`", program)
+        let bad_description = || {
+            format!("Bad description `{}`: Must be integer `x:y`", program)
+        };
+
+        let (compute, chances) = program.split_once(':').ok_or_else(bad_description)?;
+        let compute = compute.parse::<usize>().map_err(|_| bad_description())?;
+        let chances = chances.parse::<f64>().map_err(|_| bad_description())? / 100.0;
+
+        if compute > MAX_INSTRUCTIONS / 2 {
+            return Err(format!(
+                "Bad description `{}`: at most {} instructions",
+                program,
+                MAX_INSTRUCTIONS / 2
+            ));
         }
 
-        let compute = tmp[0].parse::<i32>().unwrap();
-        let chances = tmp[1].parse::<i32>().unwrap() as f32 / 100.0;
+        let proc_id = self.new_process();
 
         for _ in 0..compute {
-            if (seed::seed() as f32) < chances {
-                push_process!(self.proc_info, proc_id, DO_COMPUTE);
+            if self.rng.random() < chances {
+                push_process!(self.proc_info, proc_id, Instruction::Compute);
             } else {
-                push_process!(self.proc_info, proc_id, DO_IO);
-                push_process!(self.proc_info, proc_id, DO_IO_DONE);
+                push_process!(self.proc_info, proc_id, Instruction::Io);
+                push_process!(self.proc_info, proc_id, Instruction::IoDone);
             }
         }
+        Ok(proc_id)
     }
 
     fn move_to_ready(&mut self, pid: i32, expected: ProcessState) {
-        if pid == -1 {
-            self.curr_proc;
-        }
+        let pid = if pid == -1 { self.curr_proc } else { pid };
         assert_eq!(self.proc_info[&pid].proc_state, expected);
         assign_state!(
-            self.proc_info, 
-            pid, 
+            self.proc_info,
+            pid,
             ProcessState::Ready
         );
     }
 
+    fn move_to_wait(&mut self, expected: ProcessState) {
+        assert_eq!(self.proc_info[&self.curr_proc].proc_state, expected);
+        assign_state!(
+            self.proc_info,
+            self.curr_proc,
+            ProcessState::Blocked
+        );
+    }
+
     fn move_to_running (&mut self, expected: ProcessState) {
         assert_eq!(self.proc_info[&self.curr_proc].proc_state, expected);
         assign_state!(
-            self.proc_info, 
-            self.curr_proc, 
+            self.proc_info,
+            self.curr_proc,
             ProcessState::Running
         );
     }
@@ -104,8 +470,8 @@ impl Scheduler {
     fn move_to_done(&mut self, expected: ProcessState) {
         assert_eq!(self.proc_info[&self.curr_proc].proc_state, expected);
         assign_state!(
-            self.proc_info, 
-            self.curr_proc, 
+            self.proc_info,
+            self.curr_proc,
             ProcessState::Terminated
         );
     }
@@ -114,27 +480,55 @@ impl Scheduler {
         if pid != -1 {
             self.curr_proc = pid;
             self.move_to_running(ProcessState::Ready);
+            return;
         }
 
         let curr = self.curr_proc + 1;
         let end = self.proc_info.len() as i32;
 
-        self.state_ready_to_running(curr, end);
-        self.state_ready_to_running(0, curr);
+        let found = self.state_ready_to_running(curr, end)
+            || self.state_ready_to_running(0, curr);
+
+        match found {
+            true => self.note(format!(
+                "PID {} chosen as next Ready by scan from PID {}",
+                self.curr_proc, curr % end
+            )),
+            false => self.note(format!(
+                "no Ready process found by scan from PID {}",
+                curr % end
+            )),
+        }
+    }
+
+    // record why the scheduler did something, only kept in explain mode
+    fn note(&mut self, reason: String) {
+        if self.explain {
+            self.notes.push(reason);
+        }
     }
 
+    /// Number of processes, terminated ones included.
     pub fn get_num_processes(&self) -> usize {
         self.proc_info.len()
     }
 
+    /// The process with pid `pid`, if it was loaded or forked.
+    pub fn process(&self, pid: i32) -> Option<&ProcessStructure> {
+        self.proc_info.get(&pid)
+    }
+
+    /// Length of the code of process `pid`.
     pub fn get_num_instructions(&self, pid: i32) -> usize {
         self.proc_info[&pid].proc_code.len()
     }
 
-    // pub fn get_instruction(&self, pid: i32, idx: i32) {
-    //     self.proc_info[&pid].proc_state
-    // }
+    /// Instruction `idx` of process `pid`.
+    pub fn get_instruction(&self, pid: i32, idx: usize) -> &Instruction {
+        &self.proc_info[&pid].proc_code[idx]
+    }
 
+    /// Number of processes which have not terminated.
     pub fn get_num_actives(&self) -> usize {
         let mut result = 0;
         for p in 0..self.proc_info.len() {
@@ -147,12 +541,13 @@ impl Scheduler {
         result
     }
 
+    /// Number of processes which are ready or running.
     pub fn get_num_runnable(&self) -> usize {
         let mut result = 0;
         for p in 0..self.proc_info.len() {
             let state = self.proc_info[&(p as i32)].proc_state;
 
-            if state == ProcessState::Ready 
+            if state == ProcessState::Ready
             || state == ProcessState::Running {
                 result += 1;
             }
@@ -160,57 +555,694 @@ impl Scheduler {
         result
     }
 
-    // TODO
-    // pub fn get_ios_in_flight(&self, curr_time: i32) -> usize {
-    //     let mut flights = 0;
+    /// Number of I/Os which are still in flight at time `curr_time`.
+    pub fn get_ios_in_flight(&self, curr_time: i32) -> usize {
+        self.io_done_events
+            .iter()
+            .filter(|Reverse((time, _))| *time > curr_time)
+            .count()
+    }
+
+    /// Run every process to the end, writing the trace to `out`, and return
+    /// the statistics.
+    ///
+    /// When no process can make progress any more the run stops early and
+    /// the trace ends with a `DEADLOCK` or `STUCK` line.
+    pub fn run<W: Write>(&mut self, out: &mut W) -> io::Result<RunStats> {
+        self.run_observed(out, |_, _| {})
+    }
+
+    /// Like [`Scheduler::run`], calling `observe` with the scheduler and the
+    /// clock after every tick; without the trace, a jump over idle time
+    /// counts as one tick.
+    pub fn run_observed<W, F>(&mut self, out: &mut W, mut observe: F) -> io::Result<RunStats>
+    where
+        W: Write,
+        F: FnMut(&Scheduler, i32),
+    {
+        let mut stats = RunStats::default();
+
+        if self.proc_info.is_empty() {
+            return Ok(stats);
+        }
+
+        // make first one active
+        self.curr_proc = 0;
+        self.move_to_running(ProcessState::Ready);
+
+        self.show_modes = (0..self.proc_info.len() as i32)
+            .any(|pid| Self::has_syscall(&self.proc_info[&pid].proc_code));
+
+        // headers for each column; printed again whenever a fork adds one
+        let mut num_columns = self.get_num_processes();
+        if self.trace {
+            self.print_header(out)?;
+        }
+
+        while self.get_num_actives() > 0 {
+            self.fast_forward(&mut stats, out, &mut observe)?;
+
+            stats.total_time += 1;
+            let clock_tick = stats.total_time;
+
+            // check for io finish, in pid order when several end together
+            let mut io_done = false;
+            while let Some(&Reverse((time, pid))) = self.io_done_events.peek() {
+                if time > clock_tick {
+                    break;
+                }
+                self.io_done_events.pop();
+
+                io_done = true;
+                self.move_to_ready(pid, ProcessState::Blocked);
+                self.note(format!("PID {} finished its I/O", pid));
+
+                match self.io_done_behavior {
+                    IoDoneBehavior::IoRunImmediate => {
+                        if self.curr_proc != pid
+                        && self.proc_info[&self.curr_proc].proc_state == ProcessState::Running {
+                            self.move_to_ready(-1, ProcessState::Running);
+                            self.note(format!("PID {} preempted", self.curr_proc));
+                        }
+                        self.next_proc(pid);
+                        self.note(format!("PID {} runs because {}", pid, IO_RUN_IMMEDIATE));
+                    },
+                    IoDoneBehavior::IoRunLater => {
+                        // the process that issued the io should be run
+                        if self.process_switch_behavior == SwitchBehavior::SwitchOnEnd
+                        && self.get_num_runnable() > 1
+                        && self.proc_info[&self.curr_proc].proc_state != ProcessState::Running {
+                            self.next_proc(pid);
+                            self.note(format!(
+                                "PID {} runs because {} left the CPU idle",
+                                pid, SWITCH_ON_END
+                            ));
+                        }
+                        // this is the only thing to run: so run it
+                        if self.get_num_runnable() == 1 {
+                            self.next_proc(pid);
+                            self.note(format!("PID {} runs as the only runnable process", pid));
+                        }
+                        if self.proc_info[&pid].proc_state == ProcessState::Ready {
+                            self.note(format!("PID {} stays Ready because {}", pid, IO_RUN_LATER));
+                        }
+                    },
+                }
+                self.check_if_done();
+            }
+
+            // if current proc is RUNNING and has an instruction, execute it;
+            // a process inside a syscall keeps working in the kernel first
+            let mut instruction_to_execute = None;
+            let curr = self.proc_info.get_mut(&self.curr_proc).unwrap();
+            if curr.proc_state == ProcessState::Running && curr.proc_kernel_left > 0 {
+                instruction_to_execute = Some(Instruction::Kernel);
+                curr.proc_kernel_left -= 1;
+            } else if curr.proc_state == ProcessState::Running && curr.proc_pc < curr.proc_code.len() {
+                instruction_to_execute = Some(curr.proc_code[curr.proc_pc].clone());
+                curr.proc_pc += 1;
+
+                if instruction_to_execute == Some(Instruction::Syscall) {
+                    curr.proc_kernel_left = self.syscall_length - 1;
+                }
+            }
+
+            match instruction_to_execute {
+                Some(Instruction::Syscall) | Some(Instruction::Kernel) => {
+                    curr.proc_kernel_time += 1;
+                    stats.kernel_time += 1;
+                },
+                Some(_) => {
+                    curr.proc_user_time += 1;
+                    stats.user_time += 1;
+                },
+                None => {},
+            }
+            let kernel_left = curr.proc_kernel_left;
+
+            if instruction_to_execute.is_some() {
+                stats.cpu_busy += 1;
+            }
+
+            if self.get_ios_in_flight(clock_tick) > 0 {
+                stats.io_busy += 1;
+            }
+
+            // print what everyone is up to
+            if self.trace {
+                if num_columns != self.get_num_processes() {
+                    num_columns = self.get_num_processes();
+                    self.print_header(out)?;
+                }
+                self.print_row(out, clock_tick, io_done, instruction_to_execute.as_ref(), kernel_left)?;
+            }
+
+            match instruction_to_execute {
+                // if this is an IO start instruction, switch to waiting state
+                // and add an io completion in the future
+                Some(Instruction::Io) => {
+                    self.move_to_wait(ProcessState::Running);
+                    let done_time = clock_tick.checked_add(self.io_length + 1).ok_or_else(|| {
+                        io::Error::new(io::ErrorKind::InvalidInput, "the clock overflows before the I/O ends")
+                    })?;
+                    self.io_done_events.push(Reverse((done_time, self.curr_proc)));
+
+                    match self.process_switch_behavior {
+                        SwitchBehavior::SwitchOnIo => {
+                            self.note(format!(
+                                "PID {} issued I/O, switching because {}",
+                                self.curr_proc, SWITCH_ON_IO
+                            ));
+                            self.next_proc(-1);
+                        },
+                        SwitchBehavior::SwitchOnEnd => {
+                            self.note(format!(
+                                "PID {} issued I/O, not switching because {}",
+                                self.curr_proc, SWITCH_ON_END
+                            ));
+                        },
+                    }
+                },
+                Some(Instruction::Fork(code)) => {
+                    let child = self.fork_process(code);
+                    self.note(format!("PID {} forked PID {}", self.curr_proc, child));
+                },
+                Some(Instruction::Syscall) => {
+                    self.note(format!("PID {} trapped into the kernel", self.curr_proc));
+                },
+                Some(Instruction::Wait) => self.do_wait(),
+                Some(Instruction::Lock(lock)) => self.do_lock(lock),
+                Some(Instruction::Unlock(lock)) => self.do_unlock(lock),
+                Some(Instruction::Exit) => {
+                    let curr = self.proc_info.get_mut(&self.curr_proc).unwrap();
+                    curr.proc_pc = curr.proc_code.len();
+                    self.note(format!("PID {} exits early", self.curr_proc));
+                },
+                Some(Instruction::Kernel) if kernel_left == 0 => {
+                    self.note(format!("PID {} returns to user mode", self.curr_proc));
+                },
+                Some(_) => {},
+                None => self.note("CPU idle: nothing is running".to_string()),
+            }
+
+            // check if currently running thing is out of instructions
+            self.check_if_done();
+
+            self.print_notes(out)?;
+            observe(self, clock_tick);
 
-    //     for pid in 0..self.proc_info.len() {
-    //         for time in self.io_finish_time[&(pid as i32)] {
-    //             if *time >= curr_time {
-    //                 flights += 1;
-    //             }
-    //         }
-    //     }
-    //     flights
-    // }
+            // nothing can run and no io will ever wake anybody up
+            if self.get_num_actives() > 0
+            && self.get_num_runnable() == 0
+            && self.io_done_events.is_empty() {
+                match self.find_deadlock() {
+                    Some(cycle) => writeln!(out, "DEADLOCK: {}", self.describe_cycle(&cycle))?,
+                    None => writeln!(out, "STUCK: no process can make progress")?,
+                }
+                break;
+            }
+        }
 
-    fn check_for_switch(self) {
-        return
+        Ok(stats)
     }
 
-    fn state_ready_to_running(&mut self, start: i32, end: i32) {
+    // jump over the ticks in which nothing but time passes, up to the next
+    // io completion: the cpu idles, or the running process keeps computing
+    // (or working in the kernel) without reaching the end of its code
+    fn fast_forward<W, F>(&mut self, stats: &mut RunStats, out: &mut W, observe: &mut F) -> io::Result<()>
+    where
+        W: Write,
+        F: FnMut(&Scheduler, i32),
+    {
+        let now = stats.total_time;
+        let until_event = match self.io_done_events.peek() {
+            Some(Reverse((time, _))) => time - now - 1,
+            None => i32::MAX,
+        };
+
+        let curr = &self.proc_info[&self.curr_proc];
+        let (skip, instruction) = if curr.proc_state != ProcessState::Running {
+            match until_event {
+                i32::MAX => return Ok(()),
+                _ => (until_event, None),
+            }
+        } else if curr.proc_kernel_left > 0 {
+            (min(curr.proc_kernel_left - 1, until_event), Some(Instruction::Kernel))
+        } else {
+            let computes = curr.proc_code[curr.proc_pc.min(curr.proc_code.len())..]
+                .iter()
+                .take_while(|inst| **inst == Instruction::Compute)
+                .count() as i32;
+            (min(computes - 1, until_event), Some(Instruction::Compute))
+        };
+
+        if skip <= 0 {
+            return Ok(());
+        }
+
+        // the trace needs every tick; otherwise take the whole jump at once
+        let (steps, step) = match self.trace {
+            true => (skip, 1),
+            false => (1, skip),
+        };
+
+        for _ in 0..steps {
+            stats.total_time += step;
+            let clock_tick = stats.total_time;
+
+            let curr = self.proc_info.get_mut(&self.curr_proc).unwrap();
+            match instruction {
+                Some(Instruction::Kernel) => {
+                    curr.proc_kernel_left -= step;
+                    curr.proc_kernel_time += step;
+                    stats.kernel_time += step;
+                    stats.cpu_busy += step;
+                },
+                Some(_) => {
+                    curr.proc_pc += step as usize;
+                    curr.proc_user_time += step;
+                    stats.user_time += step;
+                    stats.cpu_busy += step;
+                },
+                None => self.note("CPU idle: nothing is running".to_string()),
+            }
+            let kernel_left = self.proc_info[&self.curr_proc].proc_kernel_left;
+
+            // every io in flight ends after the skipped ticks
+            if !self.io_done_events.is_empty() {
+                stats.io_busy += step;
+            }
+
+            if self.trace {
+                self.print_row(out, clock_tick, false, instruction.as_ref(), kernel_left)?;
+            }
+            self.print_notes(out)?;
+            observe(self, clock_tick);
+        }
+        Ok(())
+    }
+
+    fn print_row<W: Write>(
+        &self,
+        out: &mut W,
+        clock_tick: i32,
+        io_done: bool,
+        instruction: Option<&Instruction>,
+        kernel_left: i32,
+    ) -> io::Result<()> {
+        match io_done {
+            true => write!(out, "{:>3}*", clock_tick)?,
+            false => write!(out, "{:>3} ", clock_tick)?,
+        }
+
+        for pid in 0..self.proc_info.len() as i32 {
+            match instruction {
+                Some(inst) if pid == self.curr_proc => {
+                    write!(out, "{:>14}", format!("RUN:{}", inst))?
+                },
+                _ => write!(out, "{:>14}", self.proc_info[&pid].proc_state)?,
+            }
+        }
+
+        // CPU output here: if no instruction executes, output a space, otherwise a 1
+        match instruction {
+            Some(_) => write!(out, "{:>14}", "1")?,
+            None => write!(out, "{:>14}", " ")?,
+        }
+
+        // mode output here: U(ser) or K(ernel), with the transitions
+        if self.show_modes {
+            let mode = match (instruction, kernel_left) {
+                (Some(Instruction::Syscall), 0) => "U->K->U",
+                (Some(Instruction::Syscall), _) => "U->K",
+                (Some(Instruction::Kernel), 0) => "K->U",
+                (Some(Instruction::Kernel), _) => "K",
+                (Some(_), _) => "U",
+                (None, _) => " ",
+            };
+            write!(out, "{:>14}", mode)?;
+        }
+
+        // IO output here
+        let num_outstanding = self.get_ios_in_flight(clock_tick);
+        if num_outstanding > 0 {
+            write!(out, "{:>14}", num_outstanding)?;
+        } else {
+            write!(out, "{:>10}", " ")?;
+        }
+        writeln!(out)
+    }
+
+    fn print_notes<W: Write>(&mut self, out: &mut W) -> io::Result<()> {
+        if !self.notes.is_empty() {
+            if self.trace {
+                writeln!(out, "      {}", self.notes.join("; "))?;
+            }
+            self.notes.clear();
+        }
+        Ok(())
+    }
+
+    fn print_header<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        write!(out, "Time")?;
+        for pid in 0..self.proc_info.len() {
+            write!(out, "{:>14}", format!("PID:{:>2}", pid))?;
+        }
+        write!(out, "{:>14}", "CPU")?;
+        if self.show_modes {
+            write!(out, "{:>14}", "Mode")?;
+        }
+        write!(out, "{:>14}", "IOs")?;
+        writeln!(out)
+    }
+
+    fn check_if_done(&mut self) {
+        let curr = &self.proc_info[&self.curr_proc];
+
+        if curr.proc_pc >= curr.proc_code.len()
+        && curr.proc_kernel_left == 0
+        && curr.proc_state == ProcessState::Running {
+            self.move_to_done(ProcessState::Running);
+            self.note(format!("PID {} finished", self.curr_proc));
+            self.wake_parent(self.curr_proc);
+            self.next_proc(-1);
+        }
+    }
+
+    fn has_syscall(code: &[Instruction]) -> bool {
+        code.iter().any(|inst| match inst {
+            Instruction::Syscall => true,
+            Instruction::Fork(child) => Self::has_syscall(child),
+            _ => false,
+        })
+    }
+
+    fn fork_process(&mut self, code: Vec<Instruction>) -> i32 {
+        let parent = self.curr_proc;
+        let proc_id = self.new_process();
+
+        let child = self.proc_info.get_mut(&proc_id).unwrap();
+        child.proc_parent = Some(parent);
+        child.proc_code = code;
+
+        proc_id
+    }
+
+    fn children(&self, pid: i32) -> impl Iterator<Item = &ProcessStructure> {
+        (0..self.proc_info.len() as i32)
+            .map(|p| &self.proc_info[&p])
+            .filter(move |p| p.proc_parent == Some(pid))
+    }
+
+    fn has_live_children(&self, pid: i32) -> bool {
+        self.children(pid)
+            .any(|p| p.proc_state != ProcessState::Terminated)
+    }
+
+    // collect one terminated child of `pid`, if there is any
+    fn reap_child(&mut self, pid: i32) -> Option<i32> {
+        let zombie = self
+            .children(pid)
+            .find(|p| p.proc_state == ProcessState::Terminated && !p.proc_reaped)
+            .map(|p| p.proc_id);
+
+        if let Some(child) = zombie {
+            self.proc_info.get_mut(&child).unwrap().proc_reaped = true;
+        }
+        zombie
+    }
+
+    // a process waiting for its children always gives up the CPU,
+    // otherwise SWITCH_ON_END would never let the children run
+    fn do_wait(&mut self) {
+        if let Some(child) = self.reap_child(self.curr_proc) {
+            self.note(format!(
+                "PID {} collected PID {} which already finished",
+                self.curr_proc, child
+            ));
+        } else if self.has_live_children(self.curr_proc) {
+            self.waiting.insert(self.curr_proc);
+            self.move_to_wait(ProcessState::Running);
+            self.note(format!("PID {} waits for a child, switching", self.curr_proc));
+            self.next_proc(-1);
+        } else {
+            self.note(format!("PID {} has no child to wait for", self.curr_proc));
+        }
+    }
+
+    // like `do_wait`, a process blocked on a lock always gives up the CPU
+    fn do_lock(&mut self, lock: String) {
+        let holder = match self.lock_holder.get(&lock) {
+            Some(holder) => *holder,
+            None => {
+                self.note(format!("PID {} acquired {}({})", self.curr_proc, DO_LOCK, lock));
+                self.lock_holder.insert(lock, self.curr_proc);
+                return;
+            },
+        };
+
+        self.note(format!(
+            "PID {} blocked on {}({}) held by PID {}, switching",
+            self.curr_proc, DO_LOCK, lock, holder
+        ));
+        self.lock_waiters
+            .entry(lock)
+            .or_default()
+            .push_back(self.curr_proc);
+        self.move_to_wait(ProcessState::Running);
+        self.next_proc(-1);
+    }
+
+    fn do_unlock(&mut self, lock: String) {
+        // like pthread_mutex_unlock failing with EPERM: nothing happens
+        if self.lock_holder.get(&lock) != Some(&self.curr_proc) {
+            self.note(format!(
+                "PID {} cannot release {}({}) which it does not hold",
+                self.curr_proc, DO_LOCK, lock
+            ));
+            return;
+        }
+
+        // hand the lock straight to the first waiter
+        match self.lock_waiters.get_mut(&lock).and_then(|q| q.pop_front()) {
+            Some(waiter) => {
+                self.note(format!(
+                    "PID {} released {}({}), handing it to PID {}",
+                    self.curr_proc, DO_LOCK, lock, waiter
+                ));
+                self.lock_holder.insert(lock, waiter);
+                self.move_to_ready(waiter, ProcessState::Blocked);
+            },
+            None => {
+                self.note(format!("PID {} released {}({})", self.curr_proc, DO_LOCK, lock));
+                self.lock_holder.remove(&lock);
+            },
+        }
+    }
+
+    // edges of the wait-for graph: who `pid` is blocked on, and why
+    fn waits_for(&self, pid: i32) -> Vec<(i32, String)> {
+        let mut edges = Vec::new();
+
+        if self.waiting.contains(&pid) {
+            for child in self.children(pid) {
+                if child.proc_state != ProcessState::Terminated {
+                    edges.push((child.proc_id, format!("child PID {}", child.proc_id)));
+                }
+            }
+        }
+
+        for (lock, waiters) in self.lock_waiters.iter() {
+            if waiters.contains(&pid) {
+                let holder = self.lock_holder[lock];
+                edges.push((holder, format!("{}({}) held by PID {}", DO_LOCK, lock, holder)));
+            }
+        }
+        edges
+    }
+
+    /// Look for a cycle of processes blocked on each other, returned as the
+    /// pids on it. A process waits either for a lock held by the next one,
+    /// or in `wait` for the next one, its child, to exit; one cycle can
+    /// mix both.
+    pub fn find_deadlock(&self) -> Option<Vec<i32>> {
+        let mut explored = HashSet::new();
+
+        for pid in 0..self.proc_info.len() as i32 {
+            if let Some(cycle) = self.find_cycle(&mut vec![pid], &mut explored) {
+                return Some(cycle);
+            }
+        }
+        None
+    }
+
+    fn find_cycle(&self, path: &mut Vec<i32>, explored: &mut HashSet<i32>) -> Option<Vec<i32>> {
+        let last = *path.last().unwrap();
+        if explored.contains(&last) {
+            return None;
+        }
+
+        for (next, _) in self.waits_for(last) {
+            if let Some(pos) = path.iter().position(|&p| p == next) {
+                return Some(path[pos..].to_vec());
+            }
+
+            path.push(next);
+            if let Some(cycle) = self.find_cycle(path, explored) {
+                return Some(cycle);
+            }
+            path.pop();
+        }
+
+        explored.insert(last);
+        None
+    }
+
+    fn describe_cycle(&self, cycle: &[i32]) -> String {
+        let mut steps = Vec::new();
+
+        for (i, pid) in cycle.iter().enumerate() {
+            let next = cycle[(i + 1) % cycle.len()];
+            let (_, reason) = self
+                .waits_for(*pid)
+                .into_iter()
+                .find(|(p, _)| *p == next)
+                .unwrap();
+            steps.push(format!("PID {} waits for {}", pid, reason));
+        }
+        steps.join(", ")
+    }
+
+    fn wake_parent(&mut self, pid: i32) {
+        if let Some(parent) = self.proc_info[&pid].proc_parent {
+            if self.waiting.remove(&parent) {
+                self.proc_info.get_mut(&pid).unwrap().proc_reaped = true;
+                self.move_to_ready(parent, ProcessState::Blocked);
+                self.note(format!("PID {} woken from wait by PID {}", parent, pid));
+            }
+        }
+    }
+
+    fn state_ready_to_running(&mut self, start: i32, end: i32) -> bool {
         for i in start..end {
             if self.proc_info[&i].proc_state == ProcessState::Ready {
                 self.curr_proc = i;
                 self.move_to_running(ProcessState::Ready);
-                return;
+                return true;
             }
         }
+        false
+    }
+
+    // `budget` is how many more instructions the program may have
+    fn parse_code(program: &str, depth: usize, budget: &mut usize) -> Result<Vec<Instruction>, String> {
+        if depth > MAX_FORK_DEPTH {
+            return Err(format!("forks nested deeper than {}", MAX_FORK_DEPTH));
+        }
+
+        let mut code = Vec::new();
+
+        for p in Self::split_program(program)? {
+            let opcode = p
+                .chars()
+                .next()
+                .ok_or_else(|| format!("empty instruction in `{}`", program))?;
+            code.extend(Self::check_opcode(opcode, p, depth, budget)?);
+        }
+        Ok(code)
     }
 
-    fn check_opcode(&mut self, opcode: char, program: &str, proc_id: i32) {
-        match opcode {
+    // split on the commas which are not nested inside a fork block
+    fn split_program(program: &str) -> Result<Vec<&str>, String> {
+        let mut parts = Vec::new();
+        let mut depth = 0;
+        let mut start = 0;
+
+        for (i, ch) in program.char_indices() {
+            match ch {
+                '(' => depth += 1,
+                ')' if depth == 0 => {
+                    return Err(format!("unbalanced `)` in `{}`", program));
+                },
+                ')' => depth -= 1,
+                ',' if depth == 0 => {
+                    parts.push(&program[start..i]);
+                    start = i + 1;
+                },
+                _ => {},
+            }
+        }
+
+        if depth != 0 {
+            return Err(format!("unbalanced `(` in `{}`", program));
+        }
+        parts.push(&program[start..]);
+        Ok(parts)
+    }
+
+    fn check_opcode(
+        opcode: char,
+        program: &str,
+        depth: usize,
+        budget: &mut usize,
+    ) -> Result<Vec<Instruction>, String> {
+        // everything after the opcode, which is a single ascii letter when valid
+        let operand = &program[opcode.len_utf8()..];
+
+        let code = match opcode {
             'c' => {
-                let reg = program[1..].parse::<i32>().unwrap();
-                for _ in 0..reg {
-                    push_process!(self.proc_info, proc_id, DO_COMPUTE);
+                let reg = operand
+                    .parse::<usize>()
+                    .map_err(|_| format!("invalid compute `{}`: must be `c<number>`", program))?;
+                if reg > *budget {
+                    return Err(format!("program too long: at most {} instructions", MAX_INSTRUCTIONS));
                 }
+                vec![Instruction::Compute; reg]
             },
             'i' => {
-                push_process!(self.proc_info, proc_id, DO_IO);
-                push_process!(self.proc_info, proc_id, DO_IO_DONE);
+                // add one compute to HANDLE the I/O completion
+                vec![Instruction::Io, Instruction::IoDone]
+            },
+            'f' => {
+                let block = operand
+                    .strip_prefix('(')
+                    .and_then(|b| b.strip_suffix(')'))
+                    .ok_or_else(|| format!("invalid fork `{}`: child code must be `f(...)`", program))?;
+
+                match block.is_empty() {
+                    true => vec![Instruction::Fork(Vec::new())],
+                    false => vec![Instruction::Fork(Self::parse_code(block, depth + 1, budget)?)],
+                }
+            },
+            'w' => vec![Instruction::Wait],
+            'x' => vec![Instruction::Exit],
+            's' => vec![Instruction::Syscall],
+            'l' | 'u' => {
+                if operand.is_empty() {
+                    return Err(format!("invalid lock `{}`: lock must be named like `lm`", program));
+                }
+
+                match opcode {
+                    'l' => vec![Instruction::Lock(operand.to_string())],
+                    _ => vec![Instruction::Unlock(operand.to_string())],
+                }
             },
             _ => {
-                panic!(
-                    "invalid opcode `{}`: opcode must be 'c' or 'i'.", 
+                return Err(format!(
+                    "invalid opcode `{}`: opcode must be 'c', 'i', 'f', 'w', 'x', 'l', 'u' or 's'.",
                     opcode
-                );
+                ));
             }
+        };
+
+        if code.len() > *budget {
+            return Err(format!("program too long: at most {} instructions", MAX_INSTRUCTIONS));
         }
+        *budget -= code.len();
+        Ok(code)
     }
 }
 
-#[macro_export]
 macro_rules! push_process {
     ($proc_info:expr, $proc_id:expr, $process:expr) => {
         $proc_info
@@ -220,8 +1252,8 @@ macro_rules! push_process {
             .push($process);
     };
 }
+pub(crate) use push_process;
 
-#[macro_export]
 macro_rules! assign_state {
     ($proc_info:expr, $proc_id:expr, $state:expr) => {
         $proc_info
@@ -229,4 +1261,5 @@ macro_rules! assign_state {
             .unwrap()
             .proc_state = $state;
     };
-}
\ No newline at end of file
+}
+pub(crate) use assign_state;
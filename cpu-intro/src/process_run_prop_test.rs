@@ -0,0 +1,134 @@
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use std::io;
+
+    use proptest::collection::vec;
+    use proptest::prelude::*;
+
+    use crate::process_run::{
+        Instruction, IoDoneBehavior, ProcessState, Scheduler, SwitchBehavior,
+    };
+
+    // programs in the `-P` syntax, with forks nested up to two deep
+    fn program() -> impl Strategy<Value = String> {
+        let leaf = prop_oneof![
+            4 => (1..5u32).prop_map(|n| format!("c{}", n)),
+            3 => Just("i".to_string()),
+            1 => Just("s".to_string()),
+            1 => Just("w".to_string()),
+            1 => Just("x".to_string()),
+        ];
+        let instruction = leaf.prop_recursive(2, 8, 4, |inner| {
+            vec(inner, 0..4).prop_map(|code| format!("f({})", code.join(",")))
+        });
+
+        vec(instruction, 1..6).prop_map(|code| code.join(","))
+    }
+
+    fn switch_behavior() -> impl Strategy<Value = SwitchBehavior> {
+        prop_oneof![Just(SwitchBehavior::SwitchOnIo), Just(SwitchBehavior::SwitchOnEnd)]
+    }
+
+    fn io_done_behavior() -> impl Strategy<Value = IoDoneBehavior> {
+        prop_oneof![Just(IoDoneBehavior::IoRunLater), Just(IoDoneBehavior::IoRunImmediate)]
+    }
+
+    // cpu ticks needed to run `code` to the end, children included
+    fn work(code: &[Instruction], syscall_length: i32) -> i32 {
+        code.iter()
+            .map(|inst| match inst {
+                Instruction::Syscall => syscall_length,
+                Instruction::Fork(child) => 1 + work(child, syscall_length),
+                _ => 1,
+            })
+            .sum()
+    }
+
+    fn scheduler(
+        programs: &[String],
+        switch: SwitchBehavior,
+        io_done: IoDoneBehavior,
+        io_length: i32,
+        syscall_length: i32,
+    ) -> Scheduler {
+        let mut manager = Scheduler::with_behavior(switch, io_done, io_length);
+        manager.set_syscall_length(syscall_length);
+        for p in programs {
+            manager.load_program(p).unwrap();
+        }
+        manager
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(256))]
+
+        #[test]
+        fn prop_scheduler_invariants(
+            programs in vec(program(), 1..4),
+            switch in switch_behavior(),
+            io_done in io_done_behavior(),
+            io_length in 0..8i32,
+            syscall_length in 1..4i32,
+        ) {
+            let mut manager = scheduler(&programs, switch, io_done, io_length, syscall_length);
+            let expected_work: i32 = (0..manager.get_num_processes() as i32)
+                .map(|pid| work(manager.process(pid).unwrap().code(), syscall_length))
+                .sum();
+
+            let mut terminated = HashSet::new();
+            let mut last_tick = 0;
+            let stats = manager.run_observed(&mut io::sink(), |s, tick| {
+                assert_eq!(tick, last_tick + 1, "the trace skipped a tick");
+                last_tick = tick;
+
+                let states: Vec<ProcessState> = (0..s.get_num_processes() as i32)
+                    .map(|pid| s.process(pid).unwrap().state())
+                    .collect();
+
+                // one cpu: at most one process is running
+                let running = states.iter().filter(|&&st| st == ProcessState::Running).count();
+                assert!(running <= 1, "tick {}: {} processes running", tick, running);
+
+                for (pid, state) in states.iter().enumerate() {
+                    let p = s.process(pid as i32).unwrap();
+                    assert!(p.pc() <= p.code().len(), "tick {}: PID {} ran off its code", tick, pid);
+
+                    // terminated is absorbing
+                    if terminated.contains(&pid) {
+                        assert_eq!(*state, ProcessState::Terminated, "tick {}: PID {} came back", tick, pid);
+                    }
+                    if *state == ProcessState::Terminated {
+                        terminated.insert(pid);
+                    }
+                }
+
+                // only blocked processes can have an io in flight
+                let blocked = states.iter().filter(|&&st| st == ProcessState::Blocked).count();
+                assert!(s.get_ios_in_flight(tick) <= blocked, "tick {}: io without a blocked process", tick);
+            }).unwrap();
+
+            // every process ran to the end, so every io has completed
+            prop_assert_eq!(manager.get_num_actives(), 0);
+            prop_assert_eq!(manager.get_ios_in_flight(stats.total_time), 0);
+            prop_assert_eq!(terminated.len(), manager.get_num_processes());
+
+            // time bounds: the cpu and the disk overlap, and only a process
+            // with nothing left to run wastes a tick with both of them idle
+            prop_assert_eq!(stats.cpu_busy, stats.user_time + stats.kernel_time);
+            prop_assert!(stats.total_time >= stats.cpu_busy);
+            prop_assert!(stats.total_time >= stats.io_busy);
+            prop_assert!(
+                stats.total_time <= stats.cpu_busy + stats.io_busy + manager.get_num_processes() as i32
+            );
+            if !programs.iter().any(|p| p.contains('x')) {
+                prop_assert_eq!(stats.cpu_busy, expected_work);
+            }
+
+            // jumping over idle time without the trace gives the same answer
+            let mut quiet = scheduler(&programs, switch, io_done, io_length, syscall_length);
+            quiet.set_trace(false);
+            prop_assert_eq!(quiet.run(&mut io::sink()).unwrap(), stats);
+        }
+    }
+}
@@ -1,16 +1,16 @@
-use crate::process_run::Scheduler;
-
-use crate::seed;
-
 #[cfg(test)]
 mod tests {
-    use crate::process_run::{Scheduler, ProcessState};
+    use ostep_rng::Random;
+
+    use crate::process_run::{
+        Instruction, IoDoneBehavior, ProcessState, Scheduler, SwitchBehavior,
+    };
 
     #[test]
     fn test_load_program() {
         let program = "c7,i,c1,i,c33,i";
         for inst in program.split(',') {
-            let opcode = inst.chars().nth(0).unwrap();
+            let opcode = inst.chars().next().unwrap();
             println!("opcode: {}", opcode);
             match opcode {
                 'c' => {
@@ -33,10 +33,326 @@ mod tests {
         let proc_id = manager.new_process();
 
         assert_eq!(proc_id, 0);
-        assert_eq!(manager.proc_info.len(), 1);
-        assert_eq!(manager.proc_info[&proc_id].proc_id, 0);
-        assert_eq!(manager.proc_info[&proc_id].proc_pc, 0);
-        assert_eq!(manager.proc_info[&proc_id].proc_code.len(), 0);
-        assert_eq!(manager.proc_info[&proc_id].proc_state, ProcessState::Ready);
+        assert_eq!(manager.get_num_processes(), 1);
+        assert_eq!(manager.process(proc_id).unwrap().pid(), 0);
+        assert_eq!(manager.process(proc_id).unwrap().pc(), 0);
+        assert_eq!(manager.process(proc_id).unwrap().code().len(), 0);
+        assert_eq!(manager.process(proc_id).unwrap().state(), ProcessState::Ready);
+    }
+
+    #[test]
+    fn test_load_program_fork_block() {
+        let mut manager = Scheduler::new();
+        manager.load_program("c1,f(c2,f(i)),w,x").unwrap();
+
+        assert_eq!(
+            manager.process(0).unwrap().code(),
+            vec![
+                Instruction::Compute,
+                Instruction::Fork(vec![
+                    Instruction::Compute,
+                    Instruction::Compute,
+                    Instruction::Fork(vec![Instruction::Io, Instruction::IoDone]),
+                ]),
+                Instruction::Wait,
+                Instruction::Exit,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_run_fork_creates_child() {
+        let mut manager = Scheduler::new();
+        manager.load_program("f(c3),c1").unwrap();
+
+        let stats = manager.run(&mut Vec::new()).unwrap();
+
+        assert_eq!(manager.get_num_processes(), 2);
+        assert_eq!(manager.process(1).unwrap().parent(), Some(0));
+        assert_eq!(manager.process(1).unwrap().state(), ProcessState::Terminated);
+        assert_eq!(stats.cpu_busy, 5);
+        assert_eq!(stats.total_time, 5);
+    }
+
+    #[test]
+    fn test_run_wait_blocks_until_child_exits() {
+        let mut manager = Scheduler::new();
+        manager.load_program("f(c2),w,c1").unwrap();
+
+        let mut trace = Vec::new();
+        let stats = manager.run(&mut trace).unwrap();
+        let trace = String::from_utf8(trace).unwrap();
+        let lines: Vec<&str> = trace.lines().collect();
+
+        // the parent blocks in `wait` while the child computes
+        assert!(lines[3].contains("RUN:wait"));
+        assert!(lines[4].contains("BLOCKED") && lines[4].contains("RUN:cpu"));
+        assert!(lines[5].contains("BLOCKED") && lines[5].contains("RUN:cpu"));
+        assert!(lines[6].contains("RUN:cpu") && lines[6].contains("DONE"));
+        assert!(manager.process(1).unwrap().is_reaped());
+        assert_eq!(stats.total_time, 5);
+    }
+
+    #[test]
+    fn test_run_wait_collects_terminated_child() {
+        let mut manager = Scheduler::new();
+        manager.load_program("f(c1),i,w,c1").unwrap();
+
+        let stats = manager.run(&mut Vec::new()).unwrap();
+
+        // the child finished during the parent's io, so `wait` returns at once
+        assert!(manager.process(1).unwrap().is_reaped());
+        assert_eq!(stats.cpu_busy, 6);
+        assert_eq!(manager.get_num_actives(), 0);
+    }
+
+    #[test]
+    fn test_run_exit_skips_remaining_code() {
+        let mut manager = Scheduler::new();
+        manager.load_program("c1,x,c5").unwrap();
+
+        let stats = manager.run(&mut Vec::new()).unwrap();
+
+        assert_eq!(stats.cpu_busy, 2);
+        assert_eq!(stats.total_time, 2);
+        assert_eq!(manager.process(0).unwrap().state(), ProcessState::Terminated);
+    }
+
+    #[test]
+    fn test_run_lock_blocks_until_release() {
+        let mut manager = Scheduler::with_behavior(
+            SwitchBehavior::SwitchOnIo,
+            IoDoneBehavior::IoRunLater,
+            2,
+        );
+        manager.load_program("lm,i,um").unwrap();
+        manager.load_program("lm,c1,um").unwrap();
+
+        let mut trace = Vec::new();
+        let stats = manager.run(&mut trace).unwrap();
+        let trace = String::from_utf8(trace).unwrap();
+        let lines: Vec<&str> = trace.lines().collect();
+
+        // PID 1 blocks on `m` while PID 0 holds it across its io
+        assert!(lines[3].contains("RUN:lock(m)"));
+        assert!(lines[4].contains("BLOCKED") && !lines[4].contains("RUN"));
+        assert!(lines[7].contains("DONE") && lines[7].contains("RUN:cpu"));
+        assert_eq!(stats.total_time, 8);
+        assert_eq!(manager.find_deadlock(), None);
+    }
+
+    #[test]
+    fn test_run_reports_lock_deadlock() {
+        let mut manager = Scheduler::with_behavior(
+            SwitchBehavior::SwitchOnIo,
+            IoDoneBehavior::IoRunLater,
+            2,
+        );
+        manager.load_program("lm,i,ln,un,um").unwrap();
+        manager.load_program("ln,lm,um,un").unwrap();
+
+        let mut trace = Vec::new();
+        manager.run(&mut trace).unwrap();
+        let trace = String::from_utf8(trace).unwrap();
+
+        assert_eq!(manager.find_deadlock(), Some(vec![0, 1]));
+        assert!(trace.lines().last().unwrap().starts_with(
+            "DEADLOCK: PID 0 waits for lock(n) held by PID 1, PID 1 waits for lock(m) held by PID 0"
+        ));
+        assert_eq!(manager.get_num_actives(), 2);
+    }
+
+    #[test]
+    fn test_run_syscall_accounts_kernel_time() {
+        let mut manager = Scheduler::new();
+        manager.set_syscall_length(3);
+        manager.load_program("c2,s,c1").unwrap();
+        manager.load_program("s").unwrap();
+
+        let mut trace = Vec::new();
+        let stats = manager.run(&mut trace).unwrap();
+        let trace = String::from_utf8(trace).unwrap();
+        let lines: Vec<&str> = trace.lines().collect();
+
+        assert!(lines[0].contains("Mode"));
+        assert!(lines[3].contains("RUN:syscall") && lines[3].trim_end().ends_with("U->K"));
+        assert!(lines[4].contains("RUN:kernel") && lines[4].trim_end().ends_with("K"));
+        assert!(lines[5].contains("RUN:kernel") && lines[5].trim_end().ends_with("K->U"));
+        assert_eq!(stats.user_time, 3);
+        assert_eq!(stats.kernel_time, 6);
+        assert_eq!(stats.cpu_busy, stats.user_time + stats.kernel_time);
+        assert_eq!(manager.process(0).unwrap().kernel_time(), 3);
+        assert_eq!(manager.process(1).unwrap().user_time(), 0);
+    }
+
+    #[test]
+    fn test_run_without_syscall_has_no_mode_column() {
+        let mut manager = Scheduler::new();
+        manager.load_program("c1,i").unwrap();
+
+        let mut trace = Vec::new();
+        let stats = manager.run(&mut trace).unwrap();
+
+        assert!(!String::from_utf8(trace).unwrap().contains("Mode"));
+        assert_eq!(stats.kernel_time, 0);
+        assert_eq!(stats.user_time, stats.cpu_busy);
+    }
+
+    #[test]
+    fn test_run_explain_annotates_decisions() {
+        let mut manager = Scheduler::with_behavior(
+            SwitchBehavior::SwitchOnIo,
+            IoDoneBehavior::IoRunLater,
+            2,
+        );
+        manager.set_explain(true);
+        manager.load_program("c1,i,c1").unwrap();
+        manager.load_program("c3").unwrap();
+
+        let mut trace = Vec::new();
+        manager.run(&mut trace).unwrap();
+        let trace = String::from_utf8(trace).unwrap();
+        let lines: Vec<&str> = trace.lines().collect();
+
+        assert_eq!(
+            lines[3].trim(),
+            "PID 0 issued I/O, switching because SWITCH_ON_IO; \
+             PID 1 chosen as next Ready by scan from PID 1"
+        );
+        assert!(lines[7].trim().starts_with(
+            "PID 0 finished its I/O; PID 0 stays Ready because IO_RUN_LATER"
+        ));
+
+        // without explain mode the trace carries no annotations
+        let mut manager = Scheduler::new();
+        manager.load_program("c1,i,c1").unwrap();
+
+        let mut trace = Vec::new();
+        manager.run(&mut trace).unwrap();
+        assert!(!String::from_utf8(trace).unwrap().contains("because"));
+    }
+
+    #[test]
+    fn test_run_skips_idle_time_without_trace() {
+        let program = "c3,i,c2,s,i:c4,i,c1";
+        let build = |io_length: i32, trace: bool| {
+            let mut manager = Scheduler::with_behavior(
+                SwitchBehavior::SwitchOnEnd,
+                IoDoneBehavior::IoRunImmediate,
+                io_length,
+            );
+            manager.set_syscall_length(4);
+            manager.set_trace(trace);
+            for p in program.split(':') {
+                manager.load_program(p).unwrap();
+            }
+            manager
+        };
+
+        let mut trace = Vec::new();
+        let traced_stats = build(1000, true).run(&mut trace).unwrap();
+        let mut nothing = Vec::new();
+        let quiet_stats = build(1000, false).run(&mut nothing).unwrap();
+
+        // every tick is still in the trace, and the jumps give the same answer
+        let trace = String::from_utf8(trace).unwrap();
+        assert_eq!(trace.lines().count() as i32, traced_stats.total_time + 1);
+        assert_eq!(traced_stats, quiet_stats);
+        assert!(nothing.is_empty());
+
+        let stats = build(1_000_000, false).run(&mut nothing).unwrap();
+        assert_eq!(stats.cpu_busy, 20);
+        assert_eq!(stats.kernel_time, 4);
+        assert!(stats.total_time > 3_000_000);
+    }
+
+    #[test]
+    #[should_panic(expected = "io length must be between")]
+    fn test_with_behavior_rejects_negative_io_length() {
+        Scheduler::with_behavior(SwitchBehavior::SwitchOnIo, IoDoneBehavior::IoRunLater, -1);
+    }
+
+    #[test]
+    fn test_load_program_rejects_bad_input() {
+        let mut manager = Scheduler::new();
+
+        // inputs that used to panic or exhaust memory
+        for program in [
+            "", "c1,,i", "é", "cx", "c-1", "q", "f(c1", "f)c1(", "c1)", "fc1", "f(", "l", "u",
+            "c99999999999", "c1048576,c1",
+        ] {
+            assert!(manager.load_program(program).is_err(), "`{}` loaded", program);
+        }
+
+        let deep = format!("{}c1{}", "f(".repeat(64), ")".repeat(64));
+        assert!(manager.load_program(&deep).is_err());
+
+        // a program that fails to load leaves no process behind
+        assert_eq!(manager.get_num_processes(), 0);
+        assert_eq!(manager.load_program("f(),lé,ué"), Ok(0));
+    }
+
+    #[test]
+    fn test_load_rejects_bad_description() {
+        let mut manager = Scheduler::new();
+
+        for program in ["", "5", "5:", ":50", "5:50:1", "-1:50", "x:50", "999999999:50"] {
+            assert!(manager.load(program).is_err(), "`{}` loaded", program);
+        }
+        assert_eq!(manager.get_num_processes(), 0);
+        assert_eq!(manager.load("3:100"), Ok(0));
+        assert_eq!(manager.get_num_instructions(0), 3);
+    }
+
+    #[test]
+    fn test_run_unlock_without_holding_is_ignored() {
+        let mut manager = Scheduler::new();
+        manager.set_explain(true);
+        manager.load_program("um,c1").unwrap();
+
+        let mut trace = Vec::new();
+        let stats = manager.run(&mut trace).unwrap();
+
+        assert!(String::from_utf8(trace)
+            .unwrap()
+            .contains("PID 0 cannot release lock(m) which it does not hold"));
+        assert_eq!(stats.cpu_busy, 2);
+        assert_eq!(manager.get_num_actives(), 0);
+    }
+
+    #[test]
+    fn test_load_follows_the_seed() {
+        let trace = |seed: u64| {
+            let mut manager = Scheduler::new();
+            manager.set_rng(Random::new(seed));
+            manager.load("20:50").unwrap();
+            manager.load("20:50").unwrap();
+
+            let mut trace = Vec::new();
+            manager.run(&mut trace).unwrap();
+            String::from_utf8(trace).unwrap()
+        };
+
+        assert_eq!(trace(3), trace(3));
+        assert_ne!(trace(3), trace(4));
+    }
+
+    #[test]
+    fn test_load_matches_process_run_py() {
+        // `process-run.py -s 10 -l 8:50`: i c i c i i i c
+        let mut manager = Scheduler::new();
+        manager.set_rng(Random::python(10));
+        let pid = manager.load("8:50").unwrap();
+
+        let code: Vec<String> = (0..manager.get_num_instructions(pid))
+            .map(|idx| manager.get_instruction(pid, idx).to_string())
+            .collect();
+        let io = ["io", "io_done"];
+        let expected: Vec<&str> = [
+            &io[..], &["cpu"], &io, &["cpu"], &io, &io, &io, &["cpu"],
+        ]
+        .concat();
+
+        assert_eq!(code, expected);
     }
-}
\ No newline at end of file
+}
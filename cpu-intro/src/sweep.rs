@@ -0,0 +1,310 @@
+//! Sweeps of a workload over parameter ranges, reported as CSV.
+
+use std::fmt;
+use std::io::{self, Write};
+use std::str::FromStr;
+
+use ostep_rng::Random;
+
+use crate::process_run::{IoDoneBehavior, Scheduler, SwitchBehavior, MAX_IO_LENGTH};
+
+/// The most copies of a process a sweep of `procs` may ask for.
+pub const MAX_PROCS: i64 = 1000;
+
+/// The most points a sweep may run.
+pub const MAX_POINTS: i64 = 100_000;
+
+/// Parameters a sweep can vary.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Param {
+    /// How long an io takes.
+    IoLength,
+    /// The `y` of every `x:y` process description.
+    Percent,
+    /// Number of copies of the first process.
+    Procs,
+    /// The random seed, used like `random.seed()` in process-run.py.
+    Seed,
+}
+
+impl fmt::Display for Param {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Param::IoLength => "io_length",
+            Param::Percent => "percent",
+            Param::Procs => "procs",
+            Param::Seed => "seed",
+        };
+        f.pad(name)
+    }
+}
+
+impl FromStr for Param {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "io_length" => Ok(Param::IoLength),
+            "percent" => Ok(Param::Percent),
+            "procs" => Ok(Param::Procs),
+            "seed" => Ok(Param::Seed),
+            _ => Err(format!(
+                "bad parameter `{}`: must be io_length, percent, procs or seed",
+                s
+            )),
+        }
+    }
+}
+
+/// One swept parameter, written as `param=start:end[:step]` (end included).
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Axis {
+    /// The parameter to vary.
+    pub param: Param,
+    /// First value.
+    pub start: i64,
+    /// Last value, included if the steps land on it.
+    pub end: i64,
+    /// Distance between two values.
+    pub step: i64,
+}
+
+impl Axis {
+    /// The values the parameter takes, in order.
+    pub fn values(&self) -> Vec<i64> {
+        (self.start..=self.end).step_by(self.step as usize).collect()
+    }
+
+    // how many values there are, without making them
+    fn count(&self) -> i128 {
+        match self.start <= self.end && self.step >= 1 {
+            true => (self.end as i128 - self.start as i128) / self.step as i128 + 1,
+            false => 0,
+        }
+    }
+}
+
+impl FromStr for Axis {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bad_axis = || format!("bad sweep `{}`: must be `param=start:end[:step]`", s);
+
+        let (param, range) = s.split_once('=').ok_or_else(bad_axis)?;
+        let param = param.parse::<Param>()?;
+
+        let bounds = range
+            .split(':')
+            .map(|x| x.parse::<i64>())
+            .collect::<Result<Vec<i64>, _>>()
+            .map_err(|_| bad_axis())?;
+
+        let (start, end, step) = match bounds[..] {
+            [start, end] => (start, end, 1),
+            [start, end, step] => (start, end, step),
+            _ => return Err(bad_axis()),
+        };
+
+        if start < 0 || start > end || step < 1 {
+            return Err(format!(
+                "bad sweep `{}`: need 0 <= start <= end and step >= 1",
+                s
+            ));
+        }
+
+        Ok(Self { param, start, end, step })
+    }
+}
+
+/// Everything needed to build a scheduler, as given on the command line.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Workload {
+    /// Colon-separated programs, as for `-P`; used when not empty.
+    pub program: String,
+    /// Comma-separated `x:y` descriptions, as for `-l`.
+    pub process_list: String,
+    /// How long an io takes.
+    pub io_length: i32,
+    /// How long a syscall spends in kernel mode.
+    pub syscall_length: i32,
+    /// When to switch between processes.
+    pub process_switch_behavior: SwitchBehavior,
+    /// What to do with a process once its io has completed.
+    pub io_done_behavior: IoDoneBehavior,
+    /// Seed for the `x:y` descriptions.
+    pub seed: u64,
+}
+
+impl Workload {
+    /// Load every process into a new scheduler with its own random stream.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the io length is out of range, or a program or description
+    /// does not load.
+    pub fn scheduler(&self) -> Result<Scheduler, String> {
+        if !(0..=MAX_IO_LENGTH).contains(&self.io_length) {
+            return Err(format!("io length must be between 0 and {}", MAX_IO_LENGTH));
+        }
+
+        let mut s = Scheduler::with_behavior(
+            self.process_switch_behavior,
+            self.io_done_behavior,
+            self.io_length,
+        );
+        s.set_rng(Random::python(self.seed));
+        s.set_syscall_length(self.syscall_length);
+
+        if !self.program.is_empty() {
+            for p in self.program.split(':') {
+                s.load_program(p)?;
+            }
+        } else {
+            for p in self.process_list.split(',') {
+                s.load(p)?;
+            }
+        }
+        Ok(s)
+    }
+
+    fn with(&self, param: Param, value: i64) -> Result<Workload, String> {
+        let mut workload = self.clone();
+
+        match param {
+            Param::IoLength => {
+                workload.io_length = i32::try_from(value)
+                    .map_err(|_| format!("io_length {} does not fit in 32 bits", value))?;
+            },
+            Param::Seed => workload.seed = value as u64,
+            Param::Percent => {
+                workload.process_list = self
+                    .process_list
+                    .split(',')
+                    .map(|p| format!("{}:{}", p.split(':').next().unwrap(), value))
+                    .collect::<Vec<String>>()
+                    .join(",");
+            },
+            Param::Procs => match self.program.is_empty() {
+                true => {
+                    let first = self.process_list.split(',').next().unwrap();
+                    workload.process_list = vec![first; value as usize].join(",");
+                },
+                false => {
+                    let first = self.program.split(':').next().unwrap();
+                    workload.program = vec![first; value as usize].join(":");
+                },
+            },
+        }
+        Ok(workload)
+    }
+}
+
+/// Runs a workload once per point of one or two parameter ranges.
+pub struct Sweep {
+    base: Workload,
+    axes: Vec<Axis>,
+}
+
+impl Sweep {
+    /// A sweep of `base` over `axes`.
+    ///
+    /// # Errors
+    ///
+    /// Fails unless there are one or two distinct axes which make sense for
+    /// the workload, with at most [`MAX_PROCS`] processes and
+    /// [`MAX_POINTS`] points, and the workload itself loads.
+    pub fn new(base: Workload, axes: Vec<Axis>) -> Result<Self, String> {
+        if axes.is_empty() || axes.len() > 2 {
+            return Err("a sweep varies one or two parameters".to_string());
+        }
+
+        if axes.len() == 2 && axes[0].param == axes[1].param {
+            return Err(format!("cannot sweep `{}` twice", axes[0].param));
+        }
+
+        for axis in axes.iter() {
+            match axis.param {
+                Param::Percent if !base.program.is_empty() => {
+                    return Err("percent can only be swept with -l, not -P".to_string());
+                },
+                Param::Percent if axis.end > 100 => {
+                    return Err("percent must be between 0 and 100".to_string());
+                },
+                Param::Procs if axis.start < 1 || axis.end > MAX_PROCS => {
+                    return Err(format!("procs must be between 1 and {}", MAX_PROCS));
+                },
+                Param::IoLength if axis.start < 0 || axis.end > MAX_IO_LENGTH as i64 => {
+                    return Err(format!("io_length must be between 0 and {}", MAX_IO_LENGTH));
+                },
+                _ => {},
+            }
+        }
+
+        if axes.iter().map(|axis| axis.count()).product::<i128>() > MAX_POINTS as i128 {
+            return Err(format!("a sweep runs at most {} points", MAX_POINTS));
+        }
+
+        // catch a bad program before printing any of the csv
+        base.scheduler()?;
+
+        Ok(Self { base, axes })
+    }
+
+    /// Every combination of the swept values, first axis outermost.
+    pub fn points(&self) -> Vec<Vec<i64>> {
+        let mut points = vec![vec![]];
+
+        for axis in self.axes.iter() {
+            points = points
+                .into_iter()
+                .flat_map(|p| {
+                    axis.values().into_iter().map(move |v| {
+                        let mut point = p.clone();
+                        point.push(v);
+                        point
+                    })
+                })
+                .collect();
+        }
+        points
+    }
+
+    /// Run every point without a trace and write one CSV row of statistics
+    /// per point to `out`, after a header row.
+    pub fn run<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        for axis in self.axes.iter() {
+            write!(out, "{},", axis.param)?;
+        }
+        writeln!(out, "total_time,cpu_busy,io_busy,cpu_utilization,io_utilization")?;
+
+        for point in self.points() {
+            let mut workload = self.base.clone();
+            for (axis, value) in self.axes.iter().zip(point.iter()) {
+                workload = workload
+                    .with(axis.param, *value)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+            }
+
+            let mut s = workload
+                .scheduler()
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+            s.set_trace(false);
+            let stats = s.run(&mut io::sink())?;
+            let total = stats.total_time.max(1) as f64;
+
+            for value in point.iter() {
+                write!(out, "{},", value)?;
+            }
+            writeln!(
+                out,
+                "{},{},{},{:.2},{:.2}",
+                stats.total_time,
+                stats.cpu_busy,
+                stats.io_busy,
+                100.0 * stats.cpu_busy as f64 / total,
+                100.0 * stats.io_busy as f64 / total,
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,92 @@
+#[cfg(test)]
+mod tests {
+    use crate::process_run::{IoDoneBehavior, SwitchBehavior};
+    use crate::sweep::{Axis, Param, Sweep, Workload, MAX_POINTS, MAX_PROCS};
+
+    fn workload(program: &str, process_list: &str) -> Workload {
+        Workload {
+            program: program.to_string(),
+            process_list: process_list.to_string(),
+            io_length: 5,
+            syscall_length: 1,
+            process_switch_behavior: SwitchBehavior::SwitchOnIo,
+            io_done_behavior: IoDoneBehavior::IoRunLater,
+            seed: 0,
+        }
+    }
+
+    #[test]
+    fn test_parse_axis() {
+        let axis = "io_length=1:20:5".parse::<Axis>().unwrap();
+
+        assert_eq!(axis.param, Param::IoLength);
+        assert_eq!(axis.values(), vec![1, 6, 11, 16]);
+        assert_eq!("seed=0:2".parse::<Axis>().unwrap().values(), vec![0, 1, 2]);
+        assert!("seed=3:1".parse::<Axis>().is_err());
+        assert!("seed=0:3:0".parse::<Axis>().is_err());
+        assert!("speed=0:3".parse::<Axis>().is_err());
+        assert!("io_length".parse::<Axis>().is_err());
+    }
+
+    #[test]
+    fn test_sweep_rejects_bad_combinations() {
+        let percent = "percent=0:100".parse::<Axis>().unwrap();
+        let seed = "seed=0:1".parse::<Axis>().unwrap();
+
+        assert!(Sweep::new(workload("c1", ""), vec![percent]).is_err());
+        assert!(Sweep::new(workload("", "5:50"), vec![seed, seed]).is_err());
+        assert!(Sweep::new(workload("", "5:50"), vec![]).is_err());
+        assert!(Sweep::new(workload("", "5:50"), vec![percent, seed]).is_ok());
+    }
+
+    #[test]
+    fn test_sweep_rejects_huge_ranges() {
+        let axis = |s: &str| s.parse::<Axis>().unwrap();
+        let procs = format!("procs=1:{}", MAX_PROCS);
+        let points = format!("seed=0:{}", MAX_POINTS);
+
+        assert!(Sweep::new(workload("", "5:50"), vec![axis(&procs)]).is_ok());
+        assert!(Sweep::new(workload("", "5:50"), vec![axis("procs=1:1000000000000")]).is_err());
+        assert!(Sweep::new(workload("", "5:50"), vec![axis("io_length=0:2147483648")]).is_err());
+        assert!(Sweep::new(workload("", "5:50"), vec![axis(&points)]).is_err());
+        assert!(Sweep::new(workload("", "5:50"), vec![axis("seed=0:999"), axis("percent=0:100")]).is_err());
+        assert!(Sweep::new(workload("", "5:50"), vec![axis("seed=0:9223372036854775807")]).is_err());
+    }
+
+    #[test]
+    fn test_sweep_io_length_csv() {
+        let axes = vec!["io_length=1:3".parse::<Axis>().unwrap()];
+        let sweep = Sweep::new(workload("c1,i", ""), axes).unwrap();
+
+        let mut csv = Vec::new();
+        sweep.run(&mut csv).unwrap();
+
+        // one cpu, the io, the io length, then the io_done
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            "io_length,total_time,cpu_busy,io_busy,cpu_utilization,io_utilization\n\
+             1,4,3,1,75.00,25.00\n\
+             2,5,3,2,60.00,40.00\n\
+             3,6,3,3,50.00,50.00\n"
+        );
+    }
+
+    #[test]
+    fn test_sweep_grid_is_reproducible() {
+        let axes = vec![
+            "procs=1:2".parse::<Axis>().unwrap(),
+            "percent=0:100:50".parse::<Axis>().unwrap(),
+        ];
+        let sweep = Sweep::new(workload("", "4:50"), axes).unwrap();
+        assert_eq!(sweep.points().len(), 6);
+        assert_eq!(sweep.points()[1], vec![1, 50]);
+
+        let mut first = Vec::new();
+        let mut second = Vec::new();
+        sweep.run(&mut first).unwrap();
+        sweep.run(&mut second).unwrap();
+
+        assert_eq!(first, second);
+        assert_eq!(String::from_utf8(first).unwrap().lines().count(), 7);
+    }
+}
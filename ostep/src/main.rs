@@ -0,0 +1,48 @@
+use clap::{Parser, Subcommand};
+
+// one tool for the homework of every chapter, e.g.
+//   ostep process-run -l 5:50,5:50 -c
+//   ostep fork -s 4 -a 10 -t -c
+//   ostep generator -A "fork b(sleep 1),wait" -R
+#[derive(Parser, Debug)]
+#[command(name = "ostep", about = "Simulators for the OSTEP homework")]
+struct Args {
+    /// the random seed
+    #[arg(short = 's', long, global = true, default_value_t = 0)]
+    seed: u64,
+
+    /// compute answers for me
+    #[arg(short = 'c', long, global = true)]
+    solve: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// simulate how processes use the CPU and issue I/Os (process-run.py)
+    ProcessRun(cpu_intro::cli::Options),
+
+    /// grow a process tree with forks and exits (fork.py)
+    Fork(cpu_api::cli::ForkOptions),
+
+    /// write C programs which fork and wait (generator.py)
+    Generator(cpu_api::cli::GeneratorOptions),
+}
+
+fn main() {
+    let args = Args::parse();
+    let mut out = std::io::stdout();
+
+    let result = match &args.command {
+        Command::ProcessRun(options) => cpu_intro::cli::run(options, args.seed, args.solve, &mut out),
+        Command::Fork(options) => cpu_api::cli::fork(options, args.seed, args.solve, &mut out),
+        Command::Generator(options) => cpu_api::cli::generator(options, args.seed, args.solve, &mut out),
+    };
+
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
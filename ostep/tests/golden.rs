@@ -0,0 +1,114 @@
+// Runs every case of tests/golden/cases.txt and tests/golden/rust-only.txt
+// through the ostep binary and compares the output with the recorded
+// fixture, line by line. Every case needs a fixture.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+struct Case {
+    name: String,
+    tool: String,
+    args: Vec<String>,
+}
+
+fn golden_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("golden")
+}
+
+fn cases() -> Vec<Case> {
+    let list = ["cases.txt", "rust-only.txt"]
+        .map(|file| fs::read_to_string(golden_dir().join(file)).unwrap())
+        .concat();
+
+    list.lines()
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let mut words = line.split_whitespace().map(str::to_string);
+            Case {
+                name: words.next().unwrap(),
+                tool: words.next().expect("a case needs a tool"),
+                args: words.collect(),
+            }
+        })
+        .collect()
+}
+
+fn run(case: &Case) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_ostep"))
+        .arg(&case.tool)
+        .args(&case.args)
+        .output()
+        .unwrap();
+
+    assert!(
+        output.status.success(),
+        "{}: ostep failed: {}",
+        case.name,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap()
+}
+
+// where two outputs part, as `line N` and both sides of it
+fn first_mismatch(expected: &str, actual: &str) -> Option<String> {
+    let mut expected_lines = expected.lines();
+    let mut actual_lines = actual.lines();
+
+    for n in 1.. {
+        match (expected_lines.next(), actual_lines.next()) {
+            (None, None) => return None,
+            (e, a) if e == a => continue,
+            (e, a) => {
+                return Some(format!(
+                    "line {}:\n  expected: {}\n  actual:   {}",
+                    n,
+                    e.unwrap_or("<end of output>"),
+                    a.unwrap_or("<end of output>")
+                ))
+            },
+        }
+    }
+    unreachable!()
+}
+
+#[test]
+fn test_golden_outputs() {
+    let mut failures = Vec::new();
+    let mut checked = 0;
+
+    for case in cases() {
+        let fixture = golden_dir().join(&case.tool).join(format!("{}.out", case.name));
+        let Ok(expected) = fs::read_to_string(&fixture) else {
+            failures.push(format!("{}: no fixture {}", case.name, fixture.display()));
+            continue;
+        };
+
+        if let Some(mismatch) = first_mismatch(&expected, &run(&case)) {
+            failures.push(format!(
+                "{} (ostep {} {}), {}",
+                case.name,
+                case.tool,
+                case.args.join(" "),
+                mismatch
+            ));
+        }
+        checked += 1;
+    }
+
+    assert!(checked > 0, "no golden cases in {}", golden_dir().display());
+    assert!(failures.is_empty(), "\n{}", failures.join("\n\n"));
+}
+
+#[test]
+fn test_first_mismatch() {
+    assert_eq!(first_mismatch("a\nb\n", "a\nb\n"), None);
+    assert_eq!(
+        first_mismatch("a\nb\n", "a\nc\n"),
+        Some("line 2:\n  expected: b\n  actual:   c".to_string())
+    );
+    assert_eq!(
+        first_mismatch("a\n", "a\nb\n"),
+        Some("line 2:\n  expected: <end of output>\n  actual:   b".to_string())
+    );
+}
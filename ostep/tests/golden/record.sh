@@ -0,0 +1,46 @@
+#!/bin/sh
+# Record the fixtures of the golden cases.
+#
+#   record.sh HOMEWORK_DIR   record cases.txt with the python tools of a
+#                            checkout of
+#                            https://github.com/remzi-arpacidusseau/ostep-homework
+#   record.sh --rust         record both cases.txt and rust-only.txt with
+#                            target/debug/ostep
+set -eu
+
+here=$(cd "$(dirname "$0")" && pwd)
+root=$here/../../..
+
+if [ $# -ne 1 ]; then
+    echo "usage: $0 HOMEWORK_DIR | --rust" >&2
+    exit 1
+fi
+
+case $1 in
+    --rust) lists="$here/cases.txt $here/rust-only.txt" ;;
+    *) lists=$here/cases.txt ;;
+esac
+
+# shellcheck disable=SC2086
+cat $lists | grep -v -e '^#' -e '^$' | while read -r name tool args; do
+    case $1 in
+        --rust) cmd="$root/target/debug/ostep $tool" ;;
+        *)
+            case $tool in
+                process-run) cmd="python3 $1/cpu-intro/process-run.py" ;;
+                fork) cmd="python3 $1/cpu-api/fork.py" ;;
+            esac
+            ;;
+    esac
+
+    mkdir -p "$here/$tool"
+
+    # a case the tool cannot run keeps the fixture it had
+    # shellcheck disable=SC2086
+    if $cmd $args > "$here/$tool/$name.tmp"; then
+        mv "$here/$tool/$name.tmp" "$here/$tool/$name.out"
+    else
+        echo "$name: failed, not recorded" >&2
+        rm -f "$here/$tool/$name.tmp"
+    fi
+done
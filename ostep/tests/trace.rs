@@ -0,0 +1,54 @@
+// Traces real commands with `ostep fork -T`.
+
+#![cfg(target_os = "linux")]
+
+use std::process::{Command, Output};
+
+fn fork(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_ostep")).arg("fork").args(args).output().unwrap()
+}
+
+fn actions(output: &Output) -> Vec<String> {
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter(|line| line.starts_with("Action:"))
+        .map(str::to_string)
+        .collect()
+}
+
+#[test]
+fn test_trace_records_forks_execs_and_exits() {
+    let output = fork(&["-c", "-T", "sh", "-c", "ls > /dev/null; echo traced"]);
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(
+        actions(&output),
+        [
+            "Action: a EXECS sh",
+            "Action: a forks b",
+            "Action: b EXECS ls",
+            "Action: b EXITS",
+        ]
+    );
+
+    // what the command prints stays out of the trees
+    assert!(!String::from_utf8_lossy(&output.stdout).contains("traced"));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("traced"));
+}
+
+#[test]
+fn test_trace_replays_as_the_action_list() {
+    let traced = fork(&["-c", "-t", "-T", "sh", "-c", "ls > /dev/null; echo"]);
+    let replayed = fork(&["-c", "-t", "-A", "a=sh,a+b,b=ls,b-"]);
+
+    assert_eq!(String::from_utf8_lossy(&traced.stdout), String::from_utf8_lossy(&replayed.stdout));
+}
+
+#[test]
+fn test_trace_errors() {
+    let output = fork(&["-T", "ostep-no-such-command"]);
+    assert!(!output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stderr).trim(), "cannot run `ostep-no-such-command`");
+
+    assert!(!fork(&["-A", "a+b", "-T", "true"]).status.success());
+}
@@ -0,0 +1,84 @@
+//! The random stream shared by the simulators: one seedable generator
+//! which the simulators own, so that the same seed always gives the same
+//! workload, whatever else runs in the process.
+//!
+//! The stream is python's own, so that a seed gives the workload the
+//! original homework scripts give for it, on every platform and whatever
+//! the versions of the dependencies.
+
+#![warn(missing_docs)]
+
+mod mt19937;
+mod mt19937_test;
+mod random_test;
+
+use crate::mt19937::Mt19937;
+
+/// A seeded random stream with the operations of python's `random`
+/// module which the homework scripts use.
+///
+/// ```
+/// use ostep_rng::Random;
+///
+/// let mut rng = Random::new(10);
+/// let x = rng.random();
+/// assert!((0.0..1.0).contains(&x));
+/// assert_eq!(Random::new(10).random(), x);
+///
+/// // what `random.seed(10); random.random()` prints
+/// assert_eq!(x, 0.5714025946899135);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Random {
+    mt: Mt19937,
+}
+
+impl Default for Random {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+impl Random {
+    /// The stream python gives after `random.seed(seed)`: the same
+    /// Mersenne Twister, and the same algorithms for every operation.
+    pub fn new(seed: u64) -> Self {
+        Self {
+            mt: Mt19937::new(seed),
+        }
+    }
+
+    /// The same stream as [`Random::new`], for callers which spell out
+    /// that they give the workloads of the python scripts.
+    pub fn python(seed: u64) -> Self {
+        Self::new(seed)
+    }
+
+    /// Random float in [0, 1), like python's `random.random()`.
+    pub fn random(&mut self) -> f64 {
+        self.mt.random()
+    }
+
+    /// Random integer from `low` to `high`, both included, like python's
+    /// `random.randint(low, high)`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `low > high`.
+    pub fn randint(&mut self, low: i64, high: i64) -> i64 {
+        assert!(low <= high, "empty range for randint({}, {})", low, high);
+        let width = (high as i128 - low as i128 + 1) as u128;
+        (low as i128 + self.mt.randbelow(width) as i128) as i64
+    }
+
+    /// Random element of `list`, like python's `random.choice(list)`, or
+    /// `None` if the list is empty.
+    pub fn choice<'a, T>(&mut self, list: &'a [T]) -> Option<&'a T> {
+        if list.is_empty() {
+            return None;
+        }
+
+        let index = self.mt.randbelow(list.len() as u128) as usize;
+        Some(&list[index])
+    }
+}
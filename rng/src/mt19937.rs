@@ -0,0 +1,135 @@
+//! CPython's Mersenne Twister, as seeded by `random.seed(n)` for an int n,
+//! and the helpers `random.py` builds on top of it.
+
+const N: usize = 624;
+const M: usize = 397;
+const MATRIX_A: u32 = 0x9908_b0df;
+const UPPER_MASK: u32 = 0x8000_0000;
+const LOWER_MASK: u32 = 0x7fff_ffff;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Mt19937 {
+    state: Box<[u32; N]>,
+    index: usize,
+}
+
+impl Mt19937 {
+    // `random.seed(seed)`: the absolute value of the seed is split into
+    // 32-bit words, least significant first, and fed to init_by_array
+    pub(crate) fn new(seed: u64) -> Self {
+        let key = match seed >> 32 {
+            0 => vec![seed as u32],
+            high => vec![seed as u32, high as u32],
+        };
+
+        let mut mt = Self::init_genrand(19_650_218);
+        mt.init_by_array(&key);
+        mt
+    }
+
+    fn init_genrand(seed: u32) -> Self {
+        let mut state = Box::new([0u32; N]);
+        state[0] = seed;
+        for i in 1..N {
+            state[i] = 1_812_433_253u32
+                .wrapping_mul(state[i - 1] ^ (state[i - 1] >> 30))
+                .wrapping_add(i as u32);
+        }
+        Self { state, index: N }
+    }
+
+    fn init_by_array(&mut self, key: &[u32]) {
+        let mt = &mut self.state;
+        let (mut i, mut j) = (1, 0);
+
+        for _ in 0..N.max(key.len()) {
+            mt[i] = (mt[i] ^ (mt[i - 1] ^ (mt[i - 1] >> 30)).wrapping_mul(1_664_525))
+                .wrapping_add(key[j])
+                .wrapping_add(j as u32);
+            i += 1;
+            j += 1;
+            if i >= N {
+                mt[0] = mt[N - 1];
+                i = 1;
+            }
+            if j >= key.len() {
+                j = 0;
+            }
+        }
+
+        for _ in 0..N - 1 {
+            mt[i] = (mt[i] ^ (mt[i - 1] ^ (mt[i - 1] >> 30)).wrapping_mul(1_566_083_941))
+                .wrapping_sub(i as u32);
+            i += 1;
+            if i >= N {
+                mt[0] = mt[N - 1];
+                i = 1;
+            }
+        }
+
+        // the most significant bit is one, so the state is never all zero
+        mt[0] = 0x8000_0000;
+    }
+
+    fn twist(&mut self) {
+        let mt = &mut self.state;
+        for i in 0..N {
+            let y = (mt[i] & UPPER_MASK) | (mt[(i + 1) % N] & LOWER_MASK);
+            let mag = if y & 1 == 0 { 0 } else { MATRIX_A };
+            mt[i] = mt[(i + M) % N] ^ (y >> 1) ^ mag;
+        }
+        self.index = 0;
+    }
+
+    fn genrand_u32(&mut self) -> u32 {
+        if self.index >= N {
+            self.twist();
+        }
+
+        let mut y = self.state[self.index];
+        self.index += 1;
+
+        y ^= y >> 11;
+        y ^= (y << 7) & 0x9d2c_5680;
+        y ^= (y << 15) & 0xefc6_0000;
+        y ^ (y >> 18)
+    }
+
+    // `random.random()`: 53 random bits out of two words
+    pub(crate) fn random(&mut self) -> f64 {
+        let a = (self.genrand_u32() >> 5) as f64;
+        let b = (self.genrand_u32() >> 6) as f64;
+        (a * 67_108_864.0 + b) / 9_007_199_254_740_992.0
+    }
+
+    // `random.getrandbits(k)` for k <= 128: the words fill the result from
+    // the least significant end, and the last one drops its low bits
+    fn getrandbits(&mut self, k: u32) -> u128 {
+        let mut result = 0u128;
+        let mut left = k;
+        let mut shift = 0;
+
+        while left > 0 {
+            let mut word = self.genrand_u32();
+            if left < 32 {
+                word >>= 32 - left;
+            }
+            result |= (word as u128) << shift;
+            shift += 32;
+            left = left.saturating_sub(32);
+        }
+        result
+    }
+
+    // `random._randbelow(n)`: draw as many bits as n has until the number
+    // falls below n
+    pub(crate) fn randbelow(&mut self, n: u128) -> u128 {
+        let k = u128::BITS - n.leading_zeros();
+        loop {
+            let r = self.getrandbits(k);
+            if r < n {
+                return r;
+            }
+        }
+    }
+}
@@ -0,0 +1,57 @@
+#[cfg(test)]
+mod tests {
+    use crate::Random;
+
+    // the expected values are what python 3.11 prints after
+    // `random.seed(seed)` for the same calls
+
+    #[test]
+    fn test_python_random_matches() {
+        let mut rng = Random::python(0);
+        assert_eq!(rng.random(), 0.8444218515250481);
+        assert_eq!(rng.random(), 0.7579544029403025);
+        assert_eq!(rng.random(), 0.420571580830845);
+
+        let mut rng = Random::python(10);
+        assert_eq!(rng.random(), 0.5714025946899135);
+        assert_eq!(rng.random(), 0.4288890546751146);
+        assert_eq!(rng.random(), 0.5780913011344704);
+
+        // seeds over 32 bits take two words of key
+        let mut rng = Random::python((1 << 40) + 7);
+        assert_eq!(rng.random(), 0.6137037779936511);
+        assert_eq!(rng.random(), 0.8149162973309487);
+        assert_eq!(rng.random(), 0.9450115087592873);
+    }
+
+    #[test]
+    fn test_python_stream_survives_a_twist() {
+        let mut rng = Random::python(10);
+        let last = (0..700).map(|_| rng.random()).last();
+        assert_eq!(last, Some(0.995092256963542));
+    }
+
+    #[test]
+    fn test_python_randint_and_choice_match() {
+        let letters = ['a', 'b', 'c', 'd', 'e'];
+        let cases = [
+            (0, [3, 5, 4, 4, 3, 4, 3, 5], "bebcbaec", 7418652392550711558),
+            (10, [2, 4, 4, 3, 6, 2, 1, 5], "dcabcadb", -2673271033524735191),
+        ];
+
+        for (seed, ints, chosen, big) in cases {
+            let mut rng = Random::python(seed);
+            (0..3).for_each(|_| {
+                rng.random();
+            });
+
+            let drawn: Vec<i64> = (0..8).map(|_| rng.randint(1, 6)).collect();
+            assert_eq!(drawn, ints);
+
+            let picked: String = (0..8).map(|_| *rng.choice(&letters).unwrap()).collect();
+            assert_eq!(picked, chosen);
+
+            assert_eq!(rng.randint(i64::MIN, i64::MAX), big);
+        }
+    }
+}
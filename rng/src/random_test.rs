@@ -0,0 +1,65 @@
+#[cfg(test)]
+mod tests {
+    use crate::Random;
+
+    fn draw(rng: &mut Random) -> (Vec<f64>, Vec<i64>, Vec<char>) {
+        let letters = ['a', 'b', 'c', 'd'];
+        (
+            (0..20).map(|_| rng.random()).collect(),
+            (0..20).map(|_| rng.randint(-3, 3)).collect(),
+            (0..20).map(|_| *rng.choice(&letters).unwrap()).collect(),
+        )
+    }
+
+    #[test]
+    fn test_same_seed_same_stream() {
+        assert_eq!(draw(&mut Random::new(7)), draw(&mut Random::new(7)));
+        assert_ne!(draw(&mut Random::new(7)), draw(&mut Random::new(8)));
+        assert_eq!(draw(&mut Random::default()), draw(&mut Random::new(0)));
+        assert_eq!(draw(&mut Random::new(7)), draw(&mut Random::python(7)));
+
+        // a clone carries on from the same point
+        let mut rng = Random::new(7);
+        rng.random();
+        assert_eq!(draw(&mut rng.clone()), draw(&mut rng));
+    }
+
+    #[test]
+    fn test_values_stay_in_range() {
+        let mut rng = Random::new(1);
+
+        for _ in 0..10_000 {
+            assert!((0.0..1.0).contains(&rng.random()));
+            assert!((-2..=5).contains(&rng.randint(-2, 5)));
+            assert_eq!(rng.randint(4, 4), 4);
+        }
+
+        // extremes which used to overflow
+        let big = rng.randint(i64::MIN, i64::MAX);
+        assert!((i64::MIN..=i64::MAX).contains(&big));
+    }
+
+    #[test]
+    fn test_randint_and_choice_hit_every_value() {
+        let mut rng = Random::new(2);
+        let list = [10, 20, 30];
+        let mut seen_int = [false; 6];
+        let mut seen_choice = [false; 3];
+
+        for _ in 0..1000 {
+            seen_int[(rng.randint(0, 5)) as usize] = true;
+            let picked = rng.choice(&list).unwrap();
+            seen_choice[list.iter().position(|x| x == picked).unwrap()] = true;
+        }
+
+        assert!(seen_int.iter().all(|&s| s));
+        assert!(seen_choice.iter().all(|&s| s));
+        assert_eq!(rng.choice::<i32>(&[]), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "empty range")]
+    fn test_randint_rejects_empty_range() {
+        Random::new(0).randint(3, 2);
+    }
+}